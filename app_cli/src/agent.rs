@@ -0,0 +1,143 @@
+//! Wallet agent: a short-lived background process holding the secrets of an unlocked wallet in
+//! memory, so that the commands following `app_cli unlock` don't ask for the password.  It serves
+//! them over a Unix socket next to the wallet, only accessible by the user, until it is stopped
+//! by `app_cli lock` or its timeout expires.  The secrets are never written to the disk.
+
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+        process::CommandExt,
+    },
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, bail};
+use tracing::{info, warn};
+
+use crate::wallet::Secrets;
+
+/// Extension appended to the wallet file name to get the socket of its agent
+pub const AGENT_SOCKET_EXTENSION: &str = "agent";
+
+const REQUEST_SECRETS: &str = "secrets";
+const REQUEST_LOCK: &str = "lock";
+const READY: &str = "ready";
+
+pub fn agent_socket_path(wallet: &Path) -> PathBuf {
+    let mut path = wallet.as_os_str().to_owned();
+    path.push(".");
+    path.push(AGENT_SOCKET_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Sends `request` to the agent of the wallet at `wallet`, returning its response line or `None`
+/// if no agent is running.
+fn request_agent(wallet: &Path, request: &str) -> Result<Option<String>> {
+    let mut stream = match UnixStream::connect(agent_socket_path(wallet)) {
+        Ok(stream) => stream,
+        // A stale socket is left behind by an agent that didn't exit cleanly
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    writeln!(stream, "{request}")?;
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    Ok(Some(response))
+}
+
+/// Secrets of the wallet at `wallet`, if it is unlocked.
+pub fn agent_secrets(wallet: &Path) -> Result<Option<Secrets>> {
+    request_agent(wallet, REQUEST_SECRETS)?
+        .map(|response| Ok(serde_json::from_str(&response)?))
+        .transpose()
+}
+
+/// Stops the agent of the wallet at `wallet`.  Returns whether it was unlocked.
+pub fn stop_agent(wallet: &Path) -> Result<bool> {
+    Ok(request_agent(wallet, REQUEST_LOCK)?.is_some())
+}
+
+/// Unlocks the wallet at `wallet` for `timeout`, replacing its running agent if any.  The agent
+/// runs the hidden `agent` command of app_cli, which calls `run_agent`.
+// The agent outlives this process, so it is never waited for
+#[allow(clippy::zombie_processes)]
+pub fn start_agent(wallet: &Path, secrets: &Secrets, timeout: Duration) -> Result<()> {
+    stop_agent(wallet)?;
+    let mut agent = Command::new(env::current_exe()?)
+        .args(["agent", "--timeout", &timeout.as_secs().to_string()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        // Not interrupted along with the command that started it
+        .process_group(0)
+        .spawn()?;
+    // The secrets are passed through a pipe, never through the disk nor the command line
+    serde_json::to_writer(agent.stdin.take().expect("piped stdin"), secrets)?;
+    // The agent may log to its standard output before it is ready
+    let ready = BufReader::new(agent.stdout.take().expect("piped stdout"))
+        .lines()
+        .any(|line| line.is_ok_and(|line| line.trim() == READY));
+    if !ready {
+        bail!("Failed to start the agent of the wallet at {wallet:?}");
+    }
+    info!("Unlocked wallet at {wallet:?} for {timeout:?}");
+    Ok(())
+}
+
+/// Serves the secrets read from the standard input as the agent of the wallet at `wallet`, until
+/// it is locked or `timeout` expires.
+pub fn run_agent(wallet: &Path, timeout: Duration) -> Result<()> {
+    let secrets: Secrets = serde_json::from_reader(io::stdin())?;
+    let path = agent_socket_path(wallet);
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    println!("{READY}");
+
+    let expired_path = path.clone();
+    thread::spawn(move || {
+        thread::sleep(timeout);
+        let _ = fs::remove_file(expired_path);
+        process::exit(0);
+    });
+
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        let mut request = String::new();
+        if let Err(e) = BufReader::new(&stream).read_line(&mut request) {
+            warn!("Failed to read an agent request: {e}");
+            continue;
+        }
+        match request.trim() {
+            REQUEST_SECRETS => {
+                let mut response = serde_json::to_vec(&secrets)?;
+                response.push(b'\n');
+                if let Err(e) = stream.write_all(&response) {
+                    warn!("Failed to send the secrets: {e}");
+                }
+            }
+            REQUEST_LOCK => {
+                fs::remove_file(&path)?;
+                let _ = stream.write_all(b"locked\n");
+                return Ok(());
+            }
+            request => warn!("Unknown agent request {request:?}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Progress of an interrupted craft, persisted in a sidecar file next to the item file so that
+//! crafting the same item again resumes where it stopped instead of mining and proving anew.  The
+//! checkpoint is encrypted like the item file, as the ingredients hold the key of the item.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use commitlib::IngredientsDef;
+use craftlib::powpod::PowCheckpoint;
+use serde::{Deserialize, Serialize};
+
+use crate::wallet::FileCipher;
+
+/// Extension appended to the item file name to get its craft checkpoint file
+pub const CRAFT_CHECKPOINT_EXTENSION: &str = "crafting";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CraftCheckpoint {
+    /// Name of the recipe being crafted
+    pub recipe: String,
+    /// Mined ingredients, which hold the key of the item
+    pub ingredients: IngredientsDef,
+    /// Last step of the sequential work, if it has started
+    pub pow: Option<PowCheckpoint>,
+    /// Index of the key in the keychain, if it was derived from the master seed
+    #[serde(default)]
+    pub key_index: Option<u64>,
+}
+
+pub fn craft_checkpoint_path(item: &Path) -> PathBuf {
+    let mut path = item.as_os_str().to_owned();
+    path.push(".");
+    path.push(CRAFT_CHECKPOINT_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Returns whether `path` is a craft checkpoint file rather than an item file.
+pub fn is_craft_checkpoint_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == CRAFT_CHECKPOINT_EXTENSION)
+}
+
+pub fn load_craft_checkpoint(cipher: &FileCipher, item: &Path) -> Result<Option<CraftCheckpoint>> {
+    let path = craft_checkpoint_path(item);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(cipher.read(&path)?))
+}
+
+pub fn store_craft_checkpoint(
+    cipher: &FileCipher,
+    item: &Path,
+    checkpoint: &CraftCheckpoint,
+) -> Result<()> {
+    cipher.write(&craft_checkpoint_path(item), checkpoint)
+}
+
+pub fn remove_craft_checkpoint(item: &Path) -> Result<()> {
+    let path = craft_checkpoint_path(item);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! Lifecycle of an item commitment, persisted in a sidecar file next to the item file:
+//! pending → included → accepted or rejected.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use alloy::primitives::TxHash;
+use anyhow::{Result, bail};
+use pod2::middleware::{Params, RawValue};
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use tracing::{debug, info};
+
+use crate::{Config, load_public_item};
+
+/// Extension appended to the item file name to get its commit status file
+pub const COMMIT_STATUS_EXTENSION: &str = "commit";
+/// Time between synchronizer polls while waiting for a commitment
+const COMMIT_POLL_INTERVAL: Duration = Duration::from_secs(12);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum CommitStatus {
+    /// The payload is being built and sent
+    Pending,
+    /// The transaction carrying the payload was included in a block
+    Included { tx_hash: TxHash },
+    /// The synchronizer applied the payload at `epoch`
+    Accepted { tx_hash: TxHash, epoch: u64 },
+    /// The synchronizer rejected the payload
+    Rejected {
+        tx_hash: TxHash,
+        reasons: Vec<String>,
+    },
+}
+
+impl CommitStatus {
+    /// Returns whether the status can't change anymore
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Accepted { .. } | Self::Rejected { .. })
+    }
+}
+
+pub fn commit_status_path(item: &Path) -> PathBuf {
+    let mut path = item.as_os_str().to_owned();
+    path.push(".");
+    path.push(COMMIT_STATUS_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Returns whether `path` is a commit status file rather than an item file.
+pub fn is_commit_status_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == COMMIT_STATUS_EXTENSION)
+}
+
+pub fn load_commit_status(item: &Path) -> Result<Option<CommitStatus>> {
+    let path = commit_status_path(item);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+}
+
+pub fn store_commit_status(item: &Path, status: &CommitStatus) -> Result<()> {
+    let path = commit_status_path(item);
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, serde_json::to_vec_pretty(status)?)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+pub fn remove_commit_status(item: &Path) -> Result<()> {
+    let path = commit_status_path(item);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Moves the commit status of the item at `from` along with an item moved to `to`.
+pub fn move_commit_status(from: &Path, to: &Path) -> Result<()> {
+    let path = commit_status_path(from);
+    if path.exists() {
+        fs::rename(path, commit_status_path(to))?;
+    }
+    Ok(())
+}
+
+/// Queries the synchronizer once and updates the commit status of the item at `input`.
+pub async fn poll_commit_status(
+    params: &Params,
+    cfg: &Config,
+    input: &Path,
+) -> Result<CommitStatus> {
+    let status = match load_commit_status(input)? {
+        Some(status) => status,
+        None => bail!("Item at {} has not been committed", input.display()),
+    };
+    let tx_hash = match status {
+        CommitStatus::Included { tx_hash } => tx_hash,
+        status => return Ok(status),
+    };
+
+    let item = RawValue::from(load_public_item(input)?.item);
+    let client = cfg.sync_client(params)?;
+    let status = if let Some((epoch, _)) = client.item_provenance(&item).await? {
+        CommitStatus::Accepted { tx_hash, epoch }
+    } else {
+        let rejections = client.rejections(&tx_hash).await?;
+        if rejections.is_empty() {
+            return Ok(CommitStatus::Included { tx_hash });
+        }
+        CommitStatus::Rejected {
+            tx_hash,
+            reasons: rejections
+                .into_iter()
+                .map(|rejection| format!("{}: {}", rejection.reason, rejection.message))
+                .collect(),
+        }
+    };
+    store_commit_status(input, &status)?;
+    Ok(status)
+}
+
+/// Polls the synchronizer until the commitment of the item at `input` is accepted or rejected,
+/// or `timeout` elapses.
+pub async fn wait_commit(
+    params: &Params,
+    cfg: &Config,
+    input: &Path,
+    timeout: Duration,
+) -> Result<CommitStatus> {
+    if load_commit_status(input)?.is_none() {
+        bail!("Item at {} has not been committed", input.display());
+    }
+    let start = Instant::now();
+    loop {
+        match poll_commit_status(params, cfg, input).await {
+            Ok(status) if status.is_final() => return Ok(status),
+            Ok(status) => debug!("commit status of {}: {:?}", input.display(), status),
+            // The synchronizer may be temporarily unavailable
+            Err(e) => info!("Failed to get commit status: {e:#}"),
+        }
+        if start.elapsed() >= timeout {
+            bail!(
+                "Timeout waiting for the commitment of {} to be finalized",
+                input.display()
+            );
+        }
+        sleep(COMMIT_POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,106 @@
+//! Local replica of the synchronizer's created items set, updated incrementally from
+//! `GET /created_items_since/{epoch}` so that committing doesn't download the whole set.
+
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use anyhow::{Result, bail};
+use pod2::middleware::{Params, RawValue, Value, containers::Set};
+use serde::{Deserialize, Serialize};
+use sync_client::SyncClient;
+use tracing::{debug, info, warn};
+
+use crate::Config;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedItemsReplica {
+    // Epoch of the synchronizer state that `created_items` corresponds to
+    pub epoch: u64,
+    pub created_items: Set,
+}
+
+impl CreatedItemsReplica {
+    pub fn new(params: &Params) -> Self {
+        Self {
+            epoch: 0,
+            created_items: Set::new(params.max_depth_mt_containers, Default::default())
+                .expect("empty set"),
+        }
+    }
+
+    /// Loads the replica stored at `path`, or returns an empty one if it can't be loaded.
+    pub fn load(params: &Params, path: &Path) -> Self {
+        let load = || -> Result<Self> {
+            let file = File::open(path)?;
+            Ok(serde_json::from_reader(BufReader::new(file))?)
+        };
+        match load() {
+            Ok(replica) => replica,
+            Err(e) => {
+                if path.exists() {
+                    warn!("Ignoring created items cache at {path:?}: {e}");
+                }
+                Self::new(params)
+            }
+        }
+    }
+
+    pub fn store(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        let mut file = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(&mut file, self)?;
+        file.into_inner()?.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Fetches the items created since `self.epoch` and checks the resulting root against the
+    /// one published by the synchronizer.
+    pub async fn sync(&mut self, client: &SyncClient) -> Result<()> {
+        loop {
+            let (latest_epoch, items) = client.created_items_since(self.epoch, None).await?;
+            for item in items.iter().flatten() {
+                self.created_items.insert(&Value::from(*item))?;
+            }
+            self.epoch += items.len() as u64;
+            debug!("synced created items up to epoch {}", self.epoch);
+            if items.is_empty() || self.epoch >= latest_epoch {
+                break;
+            }
+        }
+
+        let root = client.created_items_root_at(self.epoch).await?;
+        if RawValue::from(self.created_items.commitment()) != root {
+            bail!(
+                "created items replica root doesn't match created_items_root {} at epoch {}",
+                root,
+                self.epoch
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Brings the cached replica up to date and returns the created items set.  The replica is
+/// rebuilt from scratch if it doesn't match the synchronizer, e.g. after a synchronizer reset.
+pub async fn sync_created_items(params: &Params, cfg: &Config) -> Result<Set> {
+    let path = Path::new(&cfg.created_items_cache);
+    let client = cfg.sync_client(params)?;
+    let mut replica = CreatedItemsReplica::load(params, path);
+    if let Err(e) = replica.sync(&client).await {
+        if replica.epoch == 0 {
+            return Err(e);
+        }
+        info!("Rebuilding created items replica: {e}");
+        replica = CreatedItemsReplica::new(params);
+        replica.sync(&client).await?;
+    }
+    replica.store(path)?;
+    Ok(replica.created_items)
+}
@@ -19,12 +19,13 @@ use tracing::{debug, info};
 use crate::Config;
 
 pub async fn send_payload(cfg: &Config, b: Vec<u8>) -> Result<TxHash> {
-    if cfg.priv_key.is_empty() {
+    let priv_key = &cfg.secrets()?.priv_key;
+    if priv_key.is_empty() {
         // test mode, return a mock tx_hash
         return Ok(TxHash::from([0u8; 32]));
     }
 
-    let signer: PrivateKeySigner = cfg.priv_key.parse()?;
+    let signer: PrivateKeySigner = priv_key.parse()?;
     let provider = ProviderBuilder::new()
         .wallet(signer.clone())
         .connect(&cfg.rpc_url)
@@ -174,7 +175,7 @@ mod tests {
 
     // this test is mostly to check the send_payload method isolated from the
     // rest of the AD server logic.
-    // To run it:
+    // To run it, with the wallet unlocked:
     // RUST_LOG=app=debug cargo test --release -p ad-server test_tx -- --nocapture --ignored
     #[ignore]
     #[tokio::test]
@@ -0,0 +1,320 @@
+//! Deterministic derivation of item keys from the player's master seed.  The n-th key, used to
+//! craft an item or to receive a transferred one, is derived from the seed and n, and the
+//! keychain file records what each key was used for, so that lost item files can be rebuilt from
+//! it with `recover_items`.  The seed itself is kept in the encrypted wallet.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{self, Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow, bail};
+use commitlib::{IngredientsDef, key_commitment};
+use pod2::middleware::{Hash, Params, RawValue, Value, hash_values};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    Recipe,
+    checkpoint::{CraftCheckpoint, load_craft_checkpoint, store_craft_checkpoint},
+    craft_item, load_public_item, move_to_used, store_item,
+    transfer::{claimed_item, read_json},
+    used_item_path,
+    wallet::FileCipher,
+};
+
+/// Domain separator of the derivation of item keys
+pub const ITEM_KEY_DERIVATION: &str = "item key derivation";
+
+/// Derives the key of the item at `index` from the master seed.
+pub fn derive_item_key(seed: RawValue, index: u64) -> RawValue {
+    RawValue::from(hash_values(&[
+        Value::from(seed),
+        Value::from(ITEM_KEY_DERIVATION),
+        Value::from(index as i64),
+    ]))
+}
+
+/// What a derived key was used for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum KeyUse {
+    /// Craft of the item at `output`
+    Craft {
+        recipe: String,
+        output: PathBuf,
+        inputs: Vec<PathBuf>,
+        /// Mined ingredients other than the inputs and the key, once mining is done
+        app_layer: Option<HashMap<String, Value>>,
+    },
+    /// Transfer request at `request`, claimed to `output` with the offer at `offer`
+    Claim {
+        request: PathBuf,
+        offer: Option<PathBuf>,
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRecord {
+    pub index: u64,
+    pub key_use: KeyUse,
+    /// Hash of the item, once crafted or claimed
+    pub item: Option<Hash>,
+}
+
+impl KeyRecord {
+    /// Path of the item of the key, once known
+    pub fn output(&self) -> Option<&Path> {
+        match &self.key_use {
+            KeyUse::Craft { output, .. } => Some(output),
+            KeyUse::Claim { output, .. } => output.as_deref(),
+        }
+    }
+
+    /// Whether the item at `item` is an input of the craft
+    fn consumes(&self, item: &Path) -> bool {
+        match &self.key_use {
+            KeyUse::Craft { inputs, .. } => inputs.iter().any(|input| input == item),
+            KeyUse::Claim { .. } => false,
+        }
+    }
+}
+
+pub struct Keychain {
+    path: PathBuf,
+    seed: RawValue,
+    records: Vec<KeyRecord>,
+}
+
+impl Keychain {
+    /// Opens the keychain of the keys derived from `seed` at `path`, creating it if it doesn't
+    /// exist.
+    pub fn open(path: &Path, seed: RawValue) -> Result<Self> {
+        let records = if path.exists() {
+            serde_json::from_slice(&fs::read(path)?)?
+        } else {
+            info!("Creating keychain at {path:?}");
+            Vec::new()
+        };
+        let keychain = Self {
+            path: path.to_path_buf(),
+            seed,
+            records,
+        };
+        keychain.store()?;
+        Ok(keychain)
+    }
+
+    fn store(&self) -> Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(&self.records)?)?;
+        fs::rename(tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    pub fn records(&self) -> &[KeyRecord] {
+        &self.records
+    }
+
+    pub fn key(&self, index: u64) -> RawValue {
+        derive_item_key(self.seed, index)
+    }
+
+    /// Cipher of the files holding the keys, keyed by the same master seed
+    pub fn file_cipher(&self) -> FileCipher {
+        FileCipher::new(self.seed)
+    }
+
+    fn record_mut(&mut self, index: u64) -> Result<&mut KeyRecord> {
+        self.records
+            .get_mut(index as usize)
+            .ok_or_else(|| anyhow!("No key with index {index}"))
+    }
+
+    fn push_key(&mut self, key_use: KeyUse) -> Result<(u64, RawValue)> {
+        let index = self.records.len() as u64;
+        self.records.push(KeyRecord {
+            index,
+            key_use,
+            item: None,
+        });
+        self.store()?;
+        Ok((index, self.key(index)))
+    }
+
+    /// Derives the key for a new craft, recording it before it is used so that it is never
+    /// reused.  Returns its index and the key.
+    pub fn new_key(
+        &mut self,
+        recipe: Recipe,
+        output: &Path,
+        inputs: &[PathBuf],
+    ) -> Result<(u64, RawValue)> {
+        self.push_key(KeyUse::Craft {
+            recipe: recipe.to_string(),
+            output: path::absolute(output)?,
+            inputs: inputs
+                .iter()
+                .map(path::absolute)
+                .collect::<Result<_, _>>()?,
+            app_layer: None,
+        })
+    }
+
+    /// Derives the key committed to by a new transfer request at `request`.  Returns its index
+    /// and the key.
+    pub fn new_request_key(&mut self, request: &Path) -> Result<(u64, RawValue)> {
+        self.push_key(KeyUse::Claim {
+            request: path::absolute(request)?,
+            offer: None,
+            output: None,
+        })
+    }
+
+    /// Finds the key of the transfer request committing to `commitment`.  Returns its index and
+    /// the key.
+    pub fn request_key(&self, commitment: Hash) -> Option<(u64, RawValue)> {
+        self.records
+            .iter()
+            .filter(|record| matches!(record.key_use, KeyUse::Claim { .. }))
+            .map(|record| (record.index, self.key(record.index)))
+            .find(|(_, key)| key_commitment(*key) == commitment)
+    }
+
+    pub fn set_mined(&mut self, index: u64, ingredients: &IngredientsDef) -> Result<()> {
+        let KeyUse::Craft { app_layer, .. } = &mut self.record_mut(index)?.key_use else {
+            bail!("Key {index} was not used for a craft");
+        };
+        *app_layer = Some(ingredients.app_layer.clone());
+        self.store()
+    }
+
+    /// Records the claim of `item` to `output` with the offer at `offer`, using the key at
+    /// `index`.
+    pub fn set_claimed(
+        &mut self,
+        index: u64,
+        offer: &Path,
+        output: &Path,
+        item: Hash,
+    ) -> Result<()> {
+        let record = self.record_mut(index)?;
+        let KeyUse::Claim {
+            offer: claim_offer,
+            output: claim_output,
+            ..
+        } = &mut record.key_use
+        else {
+            bail!("Key {index} was not used for a transfer request");
+        };
+        if let Some(claimed) = record.item
+            && claimed != item
+        {
+            bail!("Key {index} was used to claim item {claimed:#}, not {item:#}");
+        }
+        *claim_offer = Some(path::absolute(offer)?);
+        *claim_output = Some(path::absolute(output)?);
+        record.item = Some(item);
+        self.store()
+    }
+
+    pub fn set_crafted(&mut self, index: u64, item: Hash) -> Result<()> {
+        let record = self.record_mut(index)?;
+        if let Some(crafted) = record.item
+            && crafted != item
+        {
+            bail!("Key {index} was used for item {crafted:#}, not {item:#}");
+        }
+        record.item = Some(item);
+        self.store()
+    }
+}
+
+/// Location of an item which may have been moved into the `used` subdirectory
+fn find_item(path: &Path) -> Result<Option<PathBuf>> {
+    let used_path = used_item_path(path)?;
+    Ok([path.to_path_buf(), used_path]
+        .into_iter()
+        .find(|path| path.exists()))
+}
+
+/// Rebuilds the files of the crafted and claimed items of the keychain which are missing,
+/// deriving their keys from the master seed.  Crafts are resumed from the recorded ingredients,
+/// so that nothing needs to be mined again, and claims are proven again from their offers.
+/// Returns the paths of the rebuilt items.
+pub fn recover_items(
+    params: &Params,
+    keychain: &mut Keychain,
+    mining_progress: &(dyn Fn(u64) + Sync),
+) -> Result<Vec<PathBuf>> {
+    let cipher = keychain.file_cipher();
+    let mut recovered = Vec::new();
+    // Inputs are crafted or claimed before the items consuming them, so they are recovered first
+    for record in keychain.records().to_vec() {
+        let (Some(output), Some(item)) = (record.output(), record.item) else {
+            continue;
+        };
+        if find_item(output)?.is_some() {
+            continue;
+        }
+        info!("Recovering item {item:#} at {output:?}");
+        match &record.key_use {
+            KeyUse::Craft {
+                recipe,
+                inputs,
+                app_layer: Some(app_layer),
+                ..
+            } => {
+                let recipe: Recipe = recipe.parse()?;
+                let inputs = inputs
+                    .iter()
+                    .map(|input| {
+                        find_item(input)?.ok_or_else(|| anyhow!("Input {input:?} is lost"))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if load_craft_checkpoint(&cipher, output)?.is_none() {
+                    let input_hashes = inputs
+                        .iter()
+                        .map(|input| Ok(load_public_item(input)?.item))
+                        .collect::<Result<_>>()?;
+                    let checkpoint = CraftCheckpoint {
+                        recipe: recipe.blueprint().blueprint.clone(),
+                        ingredients: IngredientsDef {
+                            inputs: input_hashes,
+                            key: keychain.key(record.index),
+                            app_layer: app_layer.clone(),
+                        },
+                        pow: None,
+                        key_index: Some(record.index),
+                    };
+                    store_craft_checkpoint(&cipher, output, &checkpoint)?;
+                }
+                craft_item(params, keychain, recipe, output, &inputs, mining_progress)?;
+            }
+            KeyUse::Claim {
+                offer: Some(offer), ..
+            } => {
+                if !offer.exists() {
+                    bail!("Offer {offer:?} of item {item:#} is lost");
+                }
+                let crafted_item =
+                    claimed_item(params, read_json(offer)?, keychain.key(record.index))?;
+                if crafted_item.pod.item() != item {
+                    bail!("The offer at {offer:?} is not about item {item:#}");
+                }
+                store_item(&cipher, output, &crafted_item)?;
+            }
+            _ => bail!("The record of key {} is incomplete", record.index),
+        }
+        // Consumed items go back to the `used` subdirectory
+        let consumed = keychain.records().iter().any(|later| {
+            later.index > record.index && later.item.is_some() && later.consumes(output)
+        });
+        if consumed {
+            move_to_used(output, &used_item_path(output)?)?;
+        }
+        recovered.push(output.to_path_buf());
+    }
+    Ok(recovered)
+}
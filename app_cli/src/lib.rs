@@ -1,43 +1,65 @@
 use std::{
-    array, fmt,
+    array,
+    collections::HashSet,
+    fmt, fs,
     path::{Path, PathBuf},
     str::FromStr,
-    sync::Arc,
+    sync::{Arc, LazyLock, atomic::AtomicBool},
+    thread,
 };
 
-use alloy::primitives::Address;
+use alloy::primitives::{Address, TxHash};
 use anyhow::{Context as _, Result, anyhow, bail};
-use commitlib::{ItemBuilder, ItemDef, predicates::CommitPredicates};
+use commitlib::{
+    ItemBuilder, ItemDef, item_pod::ItemPod, nullifier_of_key, planner::MainPodPlanner,
+    predicates::CommitPredicates, util::set_from_hashes,
+};
 use common::{
-    payload::{Payload, PayloadProof},
+    payload::{Payload, PayloadKind, PayloadProof},
     set_from_value,
     shrink::{ShrunkMainPodSetup, shrink_compress_pod},
 };
 use craftlib::{
-    constants::{
-        AXE_BLUEPRINT, AXE_MINING_MAX, AXE_WORK, STONE_BLUEPRINT, STONE_MINING_MAX, WOOD_BLUEPRINT,
-        WOOD_MINING_MAX, WOOD_WORK, WOODEN_AXE_BLUEPRINT, WOODEN_AXE_MINING_MAX, WOODEN_AXE_WORK,
-    },
     item::{CraftBuilder, MiningRecipe},
-    powpod::PowPod,
+    powpod::{PowCheckpoint, PowPod},
     predicates::ItemPredicates,
+    rangepod::HashInRangePod,
+    recipes::{RecipeDef, RecipeRegistry},
 };
 use plonky2::field::types::Field;
 use pod2::{
     backends::plonky2::mainpod::Prover,
     frontend::{MainPod, MainPodBuilder},
     middleware::{
-        CustomPredicateBatch, DEFAULT_VD_SET, F, Params, Pod, RawValue, VDSet, containers::Set,
+        CustomPredicateBatch, DEFAULT_VD_SET, EMPTY_VALUE, F, Hash, Params, Pod, RawValue, VDSet,
+        Value, containers::Set,
     },
 };
 use pod2utils::macros::BuildContext;
 use rand::{RngCore, SeedableRng, rngs::StdRng};
 use serde::{Deserialize, Serialize};
+use sync_client::{NullifierStatus, SyncClient};
 use tracing::info;
 
-use crate::eth::send_payload;
+use crate::{
+    checkpoint::{
+        CraftCheckpoint, load_craft_checkpoint, remove_craft_checkpoint, store_craft_checkpoint,
+    },
+    commit::{CommitStatus, move_commit_status, remove_commit_status, store_commit_status},
+    created_items::sync_created_items,
+    eth::send_payload,
+    keychain::Keychain,
+    wallet::{Encrypted, FileCipher, Secrets, write_private},
+};
 
+pub mod agent;
+pub mod checkpoint;
+pub mod commit;
+pub mod created_items;
 pub mod eth;
+pub mod keychain;
+pub mod transfer;
+pub mod wallet;
 
 pub const USED_ITEM_SUBDIR_NAME: &str = "used";
 
@@ -47,12 +69,18 @@ pub struct Config {
     pub beacon_url: String,
     // The URL for the Ethereum RPC API
     pub rpc_url: String,
-    // Ethereum private key to send txs
-    pub priv_key: String,
     // The URL for the Synchronizer API
     pub sync_url: String,
     // The path to the pod storage directory
     pub pods_path: String,
+    // The path to the local replica of the synchronizer's created items
+    pub created_items_cache: String,
+    // The path to the keychain recording what the keys derived from the master seed were used for
+    pub keychain_path: String,
+    // The path to the encrypted wallet holding the Ethereum private key and the master seed
+    pub wallet_path: String,
+    // Secrets of the wallet, once unlocked with its password.  Only kept in memory
+    pub secrets: Option<Secrets>,
     // The address that receives DO update via blobs
     pub to_addr: Address,
     pub tx_watch_timeout: u64,
@@ -66,38 +94,143 @@ impl Config {
         Ok(Self {
             beacon_url: var("BEACON_URL")?,
             rpc_url: var("RPC_URL")?,
-            priv_key: var("PRIV_KEY")?,
             sync_url: var("SYNC_URL")?,
             pods_path: var("PODS_PATH")?,
+            created_items_cache: var("CREATED_ITEMS_CACHE")?,
+            keychain_path: var("KEYCHAIN_PATH")?,
+            wallet_path: var("WALLET_PATH")?,
+            secrets: None,
             to_addr: Address::from_str(&var("TO_ADDR")?)?,
             tx_watch_timeout: u64::from_str(&var("TX_WATCH_TIMEOUT")?)?,
         })
     }
+
+    /// Secrets of the wallet, which must have been unlocked
+    pub fn secrets(&self) -> Result<&Secrets> {
+        self.secrets
+            .as_ref()
+            .ok_or_else(|| anyhow!("Wallet at {} is locked", self.wallet_path))
+    }
+
+    /// Cipher of the files holding item keys, which requires the wallet to be unlocked
+    pub fn file_cipher(&self) -> Result<FileCipher> {
+        Ok(FileCipher::new(self.secrets()?.seed))
+    }
+
+    /// Keychain of the derived item keys, which requires the wallet to be unlocked
+    pub fn keychain(&self) -> Result<Keychain> {
+        Keychain::open(Path::new(&self.keychain_path), self.secrets()?.seed)
+    }
+
+    pub fn sync_client(&self, params: &Params) -> Result<SyncClient> {
+        Ok(SyncClient::new(&self.sync_url, params)?)
+    }
+}
+
+/// Identifiers of an item, readable without unlocking the wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicItem {
+    /// Hash of the item, committed on-chain when it is created
+    pub item: Hash,
+    /// Nullifier published when the item is consumed
+    pub nullifier: Hash,
 }
 
-pub fn load_item(input: &Path) -> anyhow::Result<CraftedItem> {
-    let mut file = std::fs::File::open(input)?;
-    let crafted_item: CraftedItem = serde_json::from_reader(&mut file)?;
-    crafted_item.pod.pod.verify()?;
+/// Contents of an item file.  The crafted item is encrypted as a whole: besides its definition,
+/// the public statements of its pod reveal the key of the item.
+#[derive(Serialize, Deserialize)]
+struct ItemFile {
+    #[serde(flatten)]
+    public: PublicItem,
+    crafted_item: Encrypted,
+}
+
+/// Stores `crafted_item` to the item file at `output`, its secret parts encrypted with `cipher`.
+pub fn store_item(cipher: &FileCipher, output: &Path, crafted_item: &CraftedItem) -> Result<()> {
+    let file = ItemFile {
+        public: crafted_item.public(),
+        crafted_item: cipher.encrypt(crafted_item)?,
+    };
+    write_private(output, &serde_json::to_vec_pretty(&file)?)
+}
+
+/// Loads the identifiers of the item at `input`, which doesn't need the wallet.
+pub fn load_public_item(input: &Path) -> Result<PublicItem> {
+    let file: ItemFile = serde_json::from_slice(&fs::read(input)?)
+        .with_context(|| format!("Invalid item file {input:?}"))?;
+    Ok(file.public)
+}
+
+/// Loads the item file at `input`, decrypting its secret parts with `cipher`.
+pub fn load_item(cipher: &FileCipher, input: &Path) -> anyhow::Result<CraftedItem> {
+    let file: ItemFile = serde_json::from_slice(&fs::read(input)?)
+        .with_context(|| format!("Invalid item file {input:?}"))?;
+    let crafted_item: CraftedItem = cipher
+        .decrypt(&file.crafted_item)
+        .with_context(|| format!("Failed to read the item at {input:?}"))?;
+    let pod = crafted_item.pod.pod();
+    pod.pod.verify()?;
+    if crafted_item.pod.item() != crafted_item.def.item_hash(&pod.params)? {
+        bail!("The pod at {input:?} is not about the item defined next to it");
+    }
+    if crafted_item.public() != file.public {
+        bail!("The identifiers of the item at {input:?} don't match its pod");
+    }
     Ok(crafted_item)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CraftedItem {
-    pub pod: MainPod,
+    pub pod: ItemPod,
     pub def: ItemDef,
+    // Key chosen by the recipient of a transferred item, which replaces the key of the
+    // ingredients to consume it.  `None` for items crafted locally.
+    #[serde(default)]
+    pub transfer_key: Option<RawValue>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Recipe {
-    Stone,
-    Wood,
-    Axe,
-    WoodenAxe,
+impl CraftedItem {
+    // Nullifier published when the item is consumed
+    pub fn nullifier(&self) -> Hash {
+        match self.transfer_key {
+            Some(key) => nullifier_of_key(key),
+            None => self.def.nullifier(),
+        }
+    }
+
+    // Identifiers of the item, stored in the clear next to it
+    pub fn public(&self) -> PublicItem {
+        PublicItem {
+            item: self.pod.item(),
+            nullifier: self.nullifier(),
+        }
+    }
+
+    // Recipe of the item, identified by its blueprint
+    pub fn recipe(&self) -> Result<Recipe> {
+        let blueprint = self.def.ingredients.app_layer.get("blueprint");
+        Recipe::list()
+            .into_iter()
+            .find(|recipe| blueprint == Some(&Value::from(recipe.to_string())))
+            .ok_or_else(|| anyhow!("Item {:#} has no known recipe", self.pod.item()))
+    }
 }
+
+/// Recipes that can be crafted, loaded from the file at `RECIPES_PATH` if set, or the default
+/// registry otherwise.  Must agree with the recipes of the other players for their items to
+/// verify.
+pub static RECIPES: LazyLock<RecipeRegistry> =
+    LazyLock::new(|| match dotenvy::var("RECIPES_PATH") {
+        Ok(path) => RecipeRegistry::load(Path::new(&path))
+            .unwrap_or_else(|e| panic!("invalid recipes at {path:?}: {e}")),
+        Err(_) => RecipeRegistry::default(),
+    });
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Recipe(&'static RecipeDef);
 impl Recipe {
     pub fn list() -> Vec<Recipe> {
-        vec![Recipe::Stone, Recipe::Wood, Recipe::Axe, Recipe::WoodenAxe]
+        RECIPES.recipes().iter().map(Recipe).collect()
     }
 }
 
@@ -118,36 +251,36 @@ impl fmt::Display for ProductionType {
 
 impl Recipe {
     pub fn production_type(&self) -> ProductionType {
-        match self {
-            Self::Stone => ProductionType::Mine,
-            Self::Wood => ProductionType::Mine,
-            Self::Axe => ProductionType::Craft,
-            Self::WoodenAxe => ProductionType::Craft,
+        if self.0.inputs.is_empty() {
+            ProductionType::Mine
+        } else {
+            ProductionType::Craft
         }
     }
+
+    // Definition of the recipe, from which its custom predicate is generated
+    pub fn blueprint(&self) -> &'static RecipeDef {
+        self.0
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.0.inputs.len()
+    }
 }
 impl FromStr for Recipe {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "stone" => Ok(Self::Stone),
-            "wood" => Ok(Self::Wood),
-            "axe" => Ok(Self::Axe),
-            "wooden-axe" => Ok(Self::WoodenAxe),
-            _ => Err(anyhow!("unknown recipe {s}")),
-        }
+        RECIPES
+            .get(s)
+            .map(Recipe)
+            .ok_or_else(|| anyhow!("unknown recipe {s}"))
     }
 }
 
 impl fmt::Display for Recipe {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        match self {
-            Self::Stone => write!(f, "stone"),
-            Self::Wood => write!(f, "wood"),
-            Self::Axe => write!(f, "axe"),
-            Self::WoodenAxe => write!(f, "wooden-axe"),
-        }
+        write!(f, "{}", self.0.blueprint)
     }
 }
 
@@ -159,6 +292,7 @@ fn rand_raw_value() -> RawValue {
 struct Helper {
     params: Params,
     vd_set: VDSet,
+    commit_preds: CommitPredicates,
     batches: Vec<Arc<CustomPredicateBatch>>,
 }
 
@@ -166,11 +300,12 @@ impl Helper {
     fn new(params: Params, vd_set: VDSet) -> Self {
         let commit_preds = CommitPredicates::compile(&params);
         let mut batches = commit_preds.defs.batches.clone();
-        let item_preds = ItemPredicates::compile(&params, &commit_preds);
+        let item_preds = ItemPredicates::compile(&params, &commit_preds, &RECIPES);
         batches.extend_from_slice(&item_preds.defs.batches);
         Self {
             params,
             vd_set,
+            commit_preds,
             batches,
         }
     }
@@ -179,94 +314,85 @@ impl Helper {
         &self,
         recipe: Recipe,
         item_def: ItemDef,
-        input_item_pods: Vec<MainPod>,
+        input_item_pods: Vec<ItemPod>,
         pow_pod: Option<PowPod>,
-    ) -> anyhow::Result<MainPod> {
+    ) -> anyhow::Result<ItemPod> {
         let prover = &Prover {};
-        let mut builder = MainPodBuilder::new(&self.params, &self.vd_set);
-        let mut item_builder =
-            ItemBuilder::new(BuildContext::new(&mut builder, &self.batches), &self.params);
+        let mut planner = MainPodPlanner::new(&self.params, &self.vd_set);
+        let mut item_builder = ItemBuilder::new(
+            BuildContext::new(planner.builder(), &self.batches),
+            &self.params,
+        );
 
         let mut sts_input_item_key = Vec::new();
         let mut sts_input_craft = Vec::new();
         for input_item_pod in input_item_pods {
-            let st_item_key = input_item_pod.pod.pub_statements()[0].clone();
-            sts_input_item_key.push(st_item_key);
-            let st_craft = input_item_pod.pod.pub_statements()[3].clone();
-            sts_input_craft.push(st_craft);
-            item_builder.ctx.builder.add_pod(input_item_pod);
+            let st_craft = input_item_pod.st_craft().ok_or_else(|| {
+                anyhow!(
+                    "Item {:#} has no recipe statement, so it can't be an input",
+                    input_item_pod.item()
+                )
+            })?;
+            sts_input_craft.push(st_craft.clone());
+            sts_input_item_key.push(input_item_pod.st_item_key().clone());
+            item_builder.ctx.builder.add_pod(input_item_pod.into());
         }
+        let (st_nullifiers, _nullifiers) = item_builder.st_nullifiers(sts_input_item_key)?;
+
+        info!("Proving range_pod...");
+        let range_pod = HashInRangePod::new(
+            &self.params,
+            self.vd_set.clone(),
+            recipe.blueprint().mining_max,
+            RawValue::from(item_def.ingredients.hash(&self.params)?),
+        )?;
+        let st_hash_in_range = range_pod.pub_statements()[0].clone();
+        item_builder.ctx.builder.add_pod(MainPod {
+            pod: Box::new(range_pod.clone()),
+            public_statements: range_pod.pub_statements(),
+            params: self.params.clone(),
+        });
+
+        let st_item_def = item_builder.st_item_def(item_def.clone())?;
+        let st_item_key = item_builder.st_item_key(st_item_def.clone())?;
+
+        let mut craft_builder = CraftBuilder::new(
+            BuildContext::new(planner.builder(), &self.batches),
+            &self.params,
+        );
+        // Sequential work of mined items
+        let st_pow = pow_pod.map(|pow_pod| {
+            let st_pow = pow_pod.pub_statements()[0].clone();
+            let main_pow_pod = MainPod {
+                pod: Box::new(pow_pod.clone()),
+                public_statements: pow_pod.pub_statements(),
+                params: craft_builder.params.clone(),
+            };
+            craft_builder.ctx.builder.add_pod(main_pow_pod);
+            st_pow
+        });
+        let st_work = craft_builder.st_work(&item_def, st_pow)?;
+        let st_craft = craft_builder.st_recipe(
+            recipe.blueprint(),
+            item_def,
+            st_item_def.clone(),
+            st_hash_in_range,
+            st_work,
+            sts_input_craft,
+        )?;
 
-        let (st_nullifiers, _nullifiers) = if sts_input_item_key.is_empty() {
-            item_builder.st_nullifiers(sts_input_item_key).unwrap()
-        } else {
-            // The default params don't have enough custom statement verifications to fit
-            // everything in a single pod, so we split it in two.
-            let (st_nullifiers, nullifiers) =
-                item_builder.st_nullifiers(sts_input_item_key).unwrap();
-            item_builder.ctx.builder.reveal(&st_nullifiers);
-            // Propagate sts_input_craft for use in st_craft
-            for st_input_craft in &sts_input_craft {
-                item_builder.ctx.builder.reveal(st_input_craft);
-            }
-
-            info!("Proving nullifiers_pod...");
-            let nullifiers_pod = builder.prove(prover).unwrap();
-            nullifiers_pod.pod.verify().unwrap();
-            builder = MainPodBuilder::new(&self.params, &self.vd_set);
-            item_builder =
-                ItemBuilder::new(BuildContext::new(&mut builder, &self.batches), &self.params);
-            item_builder.ctx.builder.add_pod(nullifiers_pod);
-            (st_nullifiers, nullifiers)
-        };
-
-        let mut item_builder =
-            ItemBuilder::new(BuildContext::new(&mut builder, &self.batches), &self.params);
-        let st_item_def = item_builder.st_item_def(item_def.clone()).unwrap();
-        let st_item_key = item_builder.st_item_key(st_item_def.clone()).unwrap();
-
-        let mut craft_builder =
-            CraftBuilder::new(BuildContext::new(&mut builder, &self.batches), &self.params);
-        let st_craft = match recipe {
-            Recipe::Stone => {
-                // unwrap safe since if we're at Stone, pow_pod is Some
-                let pow_pod = pow_pod.unwrap();
-                let st_pow = pow_pod.pub_statements()[0].clone();
-                let main_pow_pod = MainPod {
-                    pod: Box::new(pow_pod.clone()),
-                    public_statements: pow_pod.pub_statements(),
-                    params: craft_builder.params.clone(),
-                };
-                craft_builder.ctx.builder.add_pod(main_pow_pod);
-                craft_builder.st_is_stone(item_def, st_item_def.clone(), st_pow)?
-            }
-            Recipe::Wood => craft_builder.st_is_wood(item_def, st_item_def.clone())?,
-            Recipe::Axe => craft_builder.st_is_axe(
-                item_def,
-                st_item_def.clone(),
-                sts_input_craft[0].clone(),
-                sts_input_craft[1].clone(),
-            )?,
-            Recipe::WoodenAxe => craft_builder.st_is_wooden_axe(
-                item_def,
-                st_item_def.clone(),
-                sts_input_craft[0].clone(),
-                sts_input_craft[1].clone(),
-            )?,
-        };
-
-        builder.reveal(&st_item_key); // 0: Required for consuming via Nullifiers
-        builder.reveal(&st_item_def); // 1: Required for committing via CommitCreation
-        builder.reveal(&st_nullifiers); // 2: Required for committing via CommitCreation
-        builder.reveal(&st_craft); // 3: App layer predicate
+        let builder = planner.builder();
+        builder.reveal(&st_item_key); // Required for consuming via Nullifiers
+        builder.reveal(&st_item_def); // Required for committing via CommitCreation
+        builder.reveal(&st_nullifiers); // Required for committing via CommitCreation
+        builder.reveal(&st_craft); // App layer predicate
 
         info!("Proving item_pod");
         let start = std::time::Instant::now();
-        let item_key_pod = builder.prove(prover).unwrap();
+        let item_key_pod = planner.prove(prover)?;
         log::info!("[TIME] pod proving time: {:?}", start.elapsed());
-        item_key_pod.pod.verify().unwrap();
 
-        Ok(item_key_pod)
+        ItemPod::new(item_key_pod, &self.commit_preds)
     }
 
     fn make_commitment_pod(
@@ -274,183 +400,415 @@ impl Helper {
         crafted_item: CraftedItem,
         created_items: Set,
     ) -> anyhow::Result<MainPod> {
-        let mut builder = MainPodBuilder::new(&self.params, &self.vd_set);
-        builder.add_pod(crafted_item.pod.clone());
-
-        let mut item_builder =
-            ItemBuilder::new(BuildContext::new(&mut builder, &self.batches), &self.params);
-        let st_item_def = crafted_item.pod.public_statements[1].clone();
-        let st_nullifiers = crafted_item.pod.public_statements[2].clone();
+        let mut planner = MainPodPlanner::new(&self.params, &self.vd_set);
+        planner.builder().add_pod(crafted_item.pod.pod().clone());
+
+        let mut item_builder = ItemBuilder::new(
+            BuildContext::new(planner.builder(), &self.batches),
+            &self.params,
+        );
+        let st_item_def = crafted_item.pod.st_item_def().clone();
+        let st_nullifiers = crafted_item
+            .pod
+            .st_nullifiers()
+            .ok_or_else(|| anyhow!("Item {:#} wasn't crafted locally", crafted_item.pod.item()))?
+            .clone();
         let st_commit_creation = item_builder.st_commit_creation(
             crafted_item.def.clone(),
             st_nullifiers,
             created_items.clone(),
             st_item_def,
         )?;
-        builder.reveal(&st_commit_creation);
+        item_builder.ctx.builder.reveal(&st_commit_creation);
         let prover = &Prover {};
         info!("Proving commit_pod...");
-        let pod = builder.prove(prover)?;
-        pod.pod.verify().unwrap();
+        let pod = planner.prove(prover)?;
 
         Ok(pod)
     }
+
+    fn make_destruction_pod(
+        &self,
+        crafted_item: CraftedItem,
+        created_items: Set,
+    ) -> anyhow::Result<MainPod> {
+        let prover = &Prover {};
+        let mut planner = MainPodPlanner::new(&self.params, &self.vd_set);
+        let mut item_builder = ItemBuilder::new(
+            BuildContext::new(planner.builder(), &self.batches),
+            &self.params,
+        );
+        let item_hash = crafted_item.def.item_hash(&self.params)?;
+        let st_item_key = crafted_item.pod.st_item_key().clone();
+        item_builder.ctx.builder.add_pod(crafted_item.pod.into());
+
+        let (st_nullifiers, _nullifiers) = item_builder.st_nullifiers(vec![st_item_key])?;
+        let inputs_set = set_from_hashes(&self.params, &HashSet::from([item_hash]))?;
+        let st_commit_destruction =
+            item_builder.st_commit_destruction(inputs_set, st_nullifiers, created_items)?;
+        item_builder.ctx.builder.reveal(&st_commit_destruction);
+        info!("Proving destruction_pod...");
+        let pod = planner.prove(prover)?;
+
+        Ok(pod)
+    }
+
+    fn make_transfer_pod(
+        &self,
+        crafted_item: CraftedItem,
+        key_commitment: Hash,
+        created_items: Set,
+    ) -> anyhow::Result<MainPod> {
+        let prover = &Prover {};
+        let mut planner = MainPodPlanner::new(&self.params, &self.vd_set);
+        let mut item_builder = ItemBuilder::new(
+            BuildContext::new(planner.builder(), &self.batches),
+            &self.params,
+        );
+        let st_item_key = crafted_item.pod.st_item_key().clone();
+        item_builder.ctx.builder.add_pod(crafted_item.pod.into());
+
+        let (st_nullifiers, _nullifiers) = item_builder.st_nullifiers(vec![st_item_key])?;
+        let st_commit_transfer = item_builder.st_commit_transfer(
+            crafted_item.def,
+            key_commitment,
+            st_nullifiers,
+            created_items,
+        )?;
+        item_builder.ctx.builder.reveal(&st_commit_transfer);
+        info!("Proving transfer_pod...");
+        let pod = planner.prove(prover)?;
+
+        Ok(pod)
+    }
+
+    // Builds the pod proving the app layer predicate of the item created by transferring
+    // `crafted_item` to the owner of `key_commitment`, from the one of `crafted_item`.  Sent to
+    // the recipient so that the received item can be used as an input.
+    fn make_transferred_recipe_pod(
+        &self,
+        crafted_item: CraftedItem,
+        key_commitment: Hash,
+    ) -> anyhow::Result<MainPod> {
+        let recipe = crafted_item.recipe()?;
+        let st_original_craft = crafted_item
+            .pod
+            .st_craft()
+            .ok_or_else(|| anyhow!("Item {:#} has no recipe statement", crafted_item.pod.item()))?
+            .clone();
+        let prover = &Prover {};
+        let mut planner = MainPodPlanner::new(&self.params, &self.vd_set);
+        planner.builder().add_pod(crafted_item.pod.into());
+
+        let mut item_builder = ItemBuilder::new(
+            BuildContext::new(planner.builder(), &self.batches),
+            &self.params,
+        );
+        let st_transferred_item_def =
+            item_builder.st_transferred_item_def(crafted_item.def, key_commitment)?;
+        let mut craft_builder = CraftBuilder::new(
+            BuildContext::new(planner.builder(), &self.batches),
+            &self.params,
+        );
+        let st_craft = craft_builder.st_recipe_transferred(
+            recipe.blueprint(),
+            st_transferred_item_def,
+            st_original_craft,
+        )?;
+        craft_builder.ctx.builder.reveal(&st_craft); // App layer predicate
+        info!("Proving transferred recipe_pod...");
+        let pod = planner.prove(prover)?;
+
+        Ok(pod)
+    }
+
+    // Builds the pod of an item received in a transfer, which has no Nullifiers as it is
+    // committed by its transfer.  `recipe_pod` proves the app layer predicate of the item, as
+    // built by the sender with `make_transferred_recipe_pod`.
+    fn make_transferred_item_pod(
+        &self,
+        item_def: ItemDef,
+        key: RawValue,
+        recipe_pod: MainPod,
+    ) -> anyhow::Result<ItemPod> {
+        let prover = &Prover {};
+        let mut builder = MainPodBuilder::new(&self.params, &self.vd_set);
+        let st_craft = recipe_pod.public_statements[0].clone();
+        builder.add_pod(recipe_pod);
+        let mut item_builder =
+            ItemBuilder::new(BuildContext::new(&mut builder, &self.batches), &self.params);
+        let st_item_key = item_builder.st_transferred_item_key(item_def.clone(), key)?;
+        let st_item_def = item_builder.st_item_def(item_def)?;
+
+        builder.reveal(&st_item_key); // Required for consuming via Nullifiers
+        builder.reveal(&st_item_def); // Exposes the properties of the item
+        builder.reveal(&st_craft); // App layer predicate
+
+        info!("Proving transferred item_pod...");
+        let pod = builder.prove(prover)?;
+        pod.pod.verify()?;
+
+        ItemPod::new(pod, &self.commit_preds)
+    }
+}
+
+/// Shrinks and compresses the proof of a commitment pod to fit in a payload.
+fn shrink_payload_proof(params: &Params, pod: MainPod) -> anyhow::Result<PayloadProof> {
+    let shrunk_main_pod_build = ShrunkMainPodSetup::new(params)
+        .build()
+        .expect("successful build");
+    let shrunk_main_pod_proof = shrink_compress_pod(&shrunk_main_pod_build, pod)?;
+    Ok(PayloadProof::Plonky2(Box::new(shrunk_main_pod_proof)))
 }
 
+/// Crafts an item with `recipe` from the items at `inputs` and stores it at `output`, with a key
+/// derived from the master seed of `keychain`.  Mining is spread over all the available cores,
+/// calling `mining_progress` with the number of seeds tried.
 pub fn craft_item(
     params: &Params,
+    keychain: &mut Keychain,
     recipe: Recipe,
     output: &Path,
     inputs: &[PathBuf],
+    mining_progress: &(dyn Fn(u64) + Sync),
 ) -> anyhow::Result<()> {
     let vd_set = DEFAULT_VD_SET.clone();
-    let key = rand_raw_value();
-    info!("About to craft \"{recipe}\" with key {key:#}");
-    let (item_def, input_items, pow_pod) = match recipe {
-        Recipe::Stone => {
-            if !inputs.is_empty() {
-                bail!("{recipe} takes 0 inputs");
-            }
-            let mining_recipe = MiningRecipe::new(STONE_BLUEPRINT.to_string(), &[]);
-            let ingredients_def = mining_recipe
-                .do_mining(params, key, 0, STONE_MINING_MAX)?
-                .unwrap();
-
-            let start = std::time::Instant::now();
-            let pow_pod = PowPod::new(
-                params,
-                vd_set.clone(),
-                3, // num_iters
-                RawValue::from(ingredients_def.dict(params)?.commitment()),
-            )?;
-            log::info!("[TIME] PowPod proving time: {:?}", start.elapsed());
-            (
-                ItemDef {
-                    ingredients: ingredients_def.clone(),
-                    work: pow_pod.output,
-                },
-                vec![],
-                Some(pow_pod),
-            )
-        }
-        Recipe::Wood => {
-            if !inputs.is_empty() {
-                bail!("{recipe} takes 0 inputs");
-            }
-            let mining_recipe = MiningRecipe::new(WOOD_BLUEPRINT.to_string(), &[]);
-            let ingredients_def = mining_recipe
-                .do_mining(params, key, 0, WOOD_MINING_MAX)?
-                .unwrap();
-            (
-                ItemDef {
-                    ingredients: ingredients_def.clone(),
-                    work: WOOD_WORK,
-                },
-                vec![],
-                None,
-            )
+    let num_inputs = recipe.num_inputs();
+    if inputs.len() != num_inputs {
+        bail!("{recipe} takes {num_inputs} inputs");
+    }
+    let cipher = keychain.file_cipher();
+    let input_items = inputs
+        .iter()
+        .map(|input| load_item(&cipher, input))
+        .collect::<Result<Vec<_>>>()?;
+    for ((input, item), blueprint) in inputs
+        .iter()
+        .zip(&input_items)
+        .zip(&recipe.blueprint().inputs)
+    {
+        if item.def.ingredients.app_layer.get("blueprint") != Some(&Value::from(blueprint.as_str()))
+        {
+            bail!("{recipe} takes a {blueprint} as input, which {input:?} is not");
         }
-        Recipe::Axe => {
-            if inputs.len() != 2 {
-                bail!("{recipe} takes 2 inputs");
-            }
-            let wood = load_item(&inputs[0])?;
-            let stone = load_item(&inputs[1])?;
-            let mining_recipe = MiningRecipe::new(
-                AXE_BLUEPRINT.to_string(),
-                &[wood.def.item_hash(params)?, stone.def.item_hash(params)?],
+    }
+    let input_hashes = input_items
+        .iter()
+        .map(|item| item.def.item_hash(params))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let blueprint = recipe.blueprint();
+    let mut checkpoint = match load_craft_checkpoint(&cipher, output)? {
+        // Resume an interrupted craft of the same item
+        Some(checkpoint)
+            if checkpoint.recipe == blueprint.blueprint
+                && checkpoint.ingredients.inputs == HashSet::from_iter(input_hashes.clone()) =>
+        {
+            info!(
+                "Resuming craft of \"{recipe}\" with key {:#}",
+                checkpoint.ingredients.key
             );
-            let ingredients_def = mining_recipe
-                .do_mining(params, key, 0, AXE_MINING_MAX)?
-                .unwrap();
-            (
-                ItemDef {
-                    ingredients: ingredients_def.clone(),
-                    work: AXE_WORK,
-                },
-                vec![wood, stone],
-                None,
-            )
+            checkpoint
         }
-        Recipe::WoodenAxe => {
-            if inputs.len() != 2 {
-                bail!("{recipe} takes 2 inputs");
-            }
-            let wood1 = load_item(&inputs[0])?;
-            let wood2 = load_item(&inputs[1])?;
-            let mining_recipe = MiningRecipe::new(
-                WOODEN_AXE_BLUEPRINT.to_string(),
-                &[wood1.def.item_hash(params)?, wood2.def.item_hash(params)?],
-            );
+        _ => {
+            let (key_index, key) = keychain.new_key(recipe, output, inputs)?;
+            info!("About to craft \"{recipe}\" with key {key:#} (index {key_index})");
+            let mining_recipe = MiningRecipe::new(blueprint.blueprint.clone(), &input_hashes);
             let ingredients_def = mining_recipe
-                .do_mining(params, key, 0, WOODEN_AXE_MINING_MAX)?
+                .do_mining_parallel(
+                    params,
+                    key,
+                    0,
+                    blueprint.mining_max,
+                    thread::available_parallelism().map_or(1, |n| n.get()),
+                    &AtomicBool::new(false),
+                    mining_progress,
+                )?
                 .unwrap();
-            (
-                ItemDef {
-                    ingredients: ingredients_def.clone(),
-                    work: WOODEN_AXE_WORK,
-                },
-                vec![wood1, wood2],
-                None,
-            )
+            keychain.set_mined(key_index, &ingredients_def)?;
+            let checkpoint = CraftCheckpoint {
+                recipe: blueprint.blueprint.clone(),
+                ingredients: ingredients_def,
+                pow: None,
+                key_index: Some(key_index),
+            };
+            store_craft_checkpoint(&cipher, output, &checkpoint)?;
+            checkpoint
         }
     };
+    let ingredients_def = checkpoint.ingredients.clone();
+    let key_index = checkpoint.key_index;
+
+    // Sequential work of the blueprint, if any, checkpointed after each iteration
+    let pow_pod = if blueprint.pow_iters == 0 {
+        None
+    } else {
+        let start = std::time::Instant::now();
+        let pow_input = RawValue::from(ingredients_def.dict(params)?.commitment());
+        let pow_checkpoint = checkpoint.pow.take();
+        let store_pow_checkpoint = |pow: &PowCheckpoint| {
+            checkpoint.pow = Some(pow.clone());
+            store_craft_checkpoint(&cipher, output, &checkpoint)
+        };
+        let pow_pod = match pow_checkpoint {
+            Some(pow_checkpoint) => {
+                info!(
+                    "Resuming sequential work at iteration {}/{}",
+                    pow_checkpoint.count, blueprint.pow_iters
+                );
+                PowPod::resume(
+                    params,
+                    vd_set.clone(),
+                    blueprint.pow_iters,
+                    pow_input,
+                    pow_checkpoint,
+                    store_pow_checkpoint,
+                )?
+            }
+            None => PowPod::new_with_checkpoints(
+                params,
+                vd_set.clone(),
+                blueprint.pow_iters,
+                pow_input,
+                store_pow_checkpoint,
+            )?,
+        };
+        log::info!("[TIME] PowPod proving time: {:?}", start.elapsed());
+        Some(pow_pod)
+    };
+    let item_def = ItemDef {
+        ingredients: ingredients_def,
+        work: pow_pod
+            .as_ref()
+            .map_or(EMPTY_VALUE, |pow_pod| pow_pod.output),
+    };
 
     let helper = Helper::new(params.clone(), vd_set);
     let input_item_pods: Vec<_> = input_items.iter().map(|item| &item.pod).cloned().collect();
     let pod = helper.make_item_pod(recipe, item_def.clone(), input_item_pods, pow_pod)?;
 
-    let crafted_item = CraftedItem { pod, def: item_def };
-    let mut file = std::fs::File::create(output)?;
-    serde_json::to_writer(&mut file, &crafted_item)?;
+    let crafted_item = CraftedItem {
+        pod,
+        def: item_def,
+        transfer_key: None,
+    };
+    store_item(&cipher, output, &crafted_item)?;
     info!("Stored crafted item mined with recipe {recipe} to {output:?}");
+    if let Some(key_index) = key_index {
+        keychain.set_crafted(key_index, crafted_item.pod.item())?;
+    }
+    remove_craft_checkpoint(output)?;
 
     Ok(())
 }
 
-pub async fn commit_item(params: &Params, cfg: &Config, input: &Path) -> anyhow::Result<()> {
-    let mut file = std::fs::File::open(input)?;
-    let crafted_item: CraftedItem = serde_json::from_reader(&mut file)?;
+/// Verifies the item against the synchronizer: it must have been created and not consumed.
+/// Returns the epoch at which the item was verified to exist.
+pub async fn verify_item(params: &Params, cfg: &Config, public: &PublicItem) -> Result<u64> {
+    let client = cfg.sync_client(params)?;
+    let item = RawValue::from(public.item);
+    let epoch = client.verify_created_item(&item).await?;
+    info!("Verified commitment of item {item:#} via synchronizer at epoch {epoch}");
+
+    let nullifier = RawValue::from(public.nullifier);
+    if let NullifierStatus::Spent { epoch } = client.verify_nullifier(&nullifier).await? {
+        bail!("Item {item:#} was consumed by epoch {epoch}");
+    }
+    Ok(epoch)
+}
+
+/// Commits the item at `input` on-chain, tracking the commitment in its commit status file.
+/// Returns the hash of the transaction carrying the payload.
+pub async fn commit_item(params: &Params, cfg: &Config, input: &Path) -> anyhow::Result<TxHash> {
+    let crafted_item = load_item(&cfg.file_cipher()?, input)?;
+    if crafted_item.transfer_key.is_some() {
+        bail!("Transferred items are committed by their transfer");
+    }
 
-    let created_items: Set =
-        reqwest::blocking::get(format!("{}/created_items", cfg.sync_url))?.json()?;
+    let created_items = sync_created_items(params, cfg).await?;
 
     let helper = Helper::new(params.clone(), DEFAULT_VD_SET.clone());
 
     let pod = helper.make_commitment_pod(crafted_item.clone(), created_items.clone())?;
 
-    let shrunk_main_pod_build = ShrunkMainPodSetup::new(params)
-        .build()
-        .expect("successful build");
-    let shrunk_main_pod_proof = shrink_compress_pod(&shrunk_main_pod_build, pod.clone()).unwrap();
-
     let st_commit_creation = pod.public_statements[0].clone();
     let nullifier_set = set_from_value(&st_commit_creation.args()[1].literal()?)?;
     let nullifiers: Vec<RawValue> = nullifier_set.set().iter().map(|v| v.raw()).collect();
     let payload_bytes = Payload {
-        proof: PayloadProof::Plonky2(Box::new(shrunk_main_pod_proof.clone())),
-        item: RawValue::from(crafted_item.def.item_hash(params)?),
+        proof: shrink_payload_proof(params, pod)?,
+        kind: PayloadKind::Creation {
+            item: RawValue::from(crafted_item.def.item_hash(params)?),
+        },
         created_items_root: RawValue::from(created_items.commitment()),
         nullifiers,
     }
     .to_bytes();
 
-    let tx_hash = send_payload(cfg, payload_bytes).await?;
+    store_commit_status(input, &CommitStatus::Pending)?;
+    let tx_hash = match send_payload(cfg, payload_bytes).await {
+        Ok(tx_hash) => tx_hash,
+        Err(e) => {
+            remove_commit_status(input)?;
+            return Err(e);
+        }
+    };
+    store_commit_status(input, &CommitStatus::Included { tx_hash })?;
 
     info!("Committed item in tx={tx_hash}");
 
-    Ok(())
+    Ok(tx_hash)
 }
 
-pub async fn destroy_item(_params: &Params, _cfg: &Config, item: &PathBuf) -> anyhow::Result<()> {
-    // TODO: Nullify
+/// Destroys the item at `item` on-chain by publishing its nullifier without creating a new
+/// item, then moves it into the `used` subdirectory.  Returns the hash of the transaction
+/// carrying the payload.
+pub async fn destroy_item(params: &Params, cfg: &Config, item: &Path) -> anyhow::Result<TxHash> {
+    let used_item = used_item_path(item)?;
+    let crafted_item = load_item(&cfg.file_cipher()?, item)?;
+    let created_items = sync_created_items(params, cfg).await?;
+
+    let helper = Helper::new(params.clone(), DEFAULT_VD_SET.clone());
+    let pod = helper.make_destruction_pod(crafted_item, created_items.clone())?;
+
+    let st_commit_destruction = pod.public_statements[0].clone();
+    let nullifier_set = set_from_value(&st_commit_destruction.args()[0].literal()?)?;
+    let nullifiers: Vec<RawValue> = nullifier_set.set().iter().map(|v| v.raw()).collect();
+    let payload_bytes = Payload {
+        proof: shrink_payload_proof(params, pod)?,
+        kind: PayloadKind::Destruction,
+        created_items_root: RawValue::from(created_items.commitment()),
+        nullifiers,
+    }
+    .to_bytes();
+
+    let tx_hash = send_payload(cfg, payload_bytes).await?;
+    info!("Destroyed item in tx={tx_hash}");
+
+    move_to_used(item, &used_item)?;
+
+    Ok(tx_hash)
+}
+
+/// Path of the item at `item` once moved into the `used` subdirectory.
+fn used_item_path(item: &Path) -> Result<PathBuf> {
     let (file_name, parent_dir) = item
         .file_name()
         .and_then(|name| Some((name.display(), item.parent()?.display())))
         .ok_or(anyhow!("Item at {} is not a file.", item.display()))?;
-    let used_item = PathBuf::from(format!("{parent_dir}/{USED_ITEM_SUBDIR_NAME}/{file_name}"));
-    std::fs::rename(item, used_item)?;
-    info!("Destroyed item at {}", item.display());
+    Ok(PathBuf::from(format!(
+        "{parent_dir}/{USED_ITEM_SUBDIR_NAME}/{file_name}"
+    )))
+}
 
+/// Moves a consumed item along with its commit status into the `used` subdirectory.
+fn move_to_used(item: &Path, used_item: &Path) -> Result<()> {
+    std::fs::create_dir_all(used_item.parent().expect("has parent"))?;
+    std::fs::rename(item, used_item)?;
+    move_commit_status(item, used_item)?;
+    info!(
+        "Moved consumed item at {} to {}",
+        item.display(),
+        used_item.display()
+    );
     Ok(())
 }
@@ -1,19 +1,53 @@
 //! Examples of usage
 //!
+//! - create the wallet, whose password is then asked by the commands using its secrets unless
+//!   it is unlocked, or read from the file at WALLET_PASSWORD_FILE if set:
+//!   RUST_LOG=app=debug cargo run --release -p app_cli -- init-wallet
+//! - unlock the wallet for an hour:
+//!   RUST_LOG=app=debug cargo run --release -p app_cli -- unlock
 //! - craft new stone item:
 //!   RUST_LOG=app=debug cargo run --release -p app_cli -- craft --output ./item0 --recipe stone
 //! - commit the crafted item:
 //!   RUST_LOG=app=debug cargo run --release -p app_cli -- commit --input ./item0
-use std::{path::PathBuf, str::FromStr};
+//! - wait until the synchronizer accepts or rejects the committed item:
+//!   RUST_LOG=app=debug cargo run --release -p app_cli -- status --input ./item0
+//! - destroy the committed item:
+//!   RUST_LOG=app=debug cargo run --release -p app_cli -- destroy --input ./item0
+//! - request an item from another player, who receives ./request0:
+//!   RUST_LOG=app=debug cargo run --release -p app_cli -- transfer-request --output ./request0
+//! - transfer the committed item to the author of ./request0, who receives ./offer0:
+//!   RUST_LOG=app=debug cargo run --release -p app_cli -- transfer --input ./item0 --request ./request0 --output ./offer0
+//! - claim the transferred item:
+//!   RUST_LOG=app=debug cargo run --release -p app_cli -- claim --offer ./offer0 --request ./request0 --output ./item1
+//! - rebuild the lost items crafted with keys from the keychain:
+//!   RUST_LOG=app=debug cargo run --release -p app_cli -- recover
+//! - lock the wallet:
+//!   RUST_LOG=app=debug cargo run --release -p app_cli -- lock
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
-use app_cli::{Config, Recipe, commit_item, craft_item, load_item};
+use alloy::primitives::TxHash;
+use anyhow::{Result, bail};
+use app_cli::{
+    Config, Recipe,
+    agent::{agent_secrets, run_agent, start_agent, stop_agent},
+    commit::{load_commit_status, wait_commit},
+    commit_item, craft_item, destroy_item,
+    keychain::recover_items,
+    load_public_item,
+    transfer::{claim_item, request_transfer, transfer_item},
+    verify_item,
+    wallet::{Secrets, create_wallet, open_wallet},
+};
 use clap::{Parser, Subcommand};
 use common::{load_dotenv, log_init};
-use pod2::{
-    backends::plonky2::primitives::merkletree::MerkleProof,
-    middleware::{Params, RawValue, containers::Set},
-};
-use tracing::info;
+use pod2::middleware::Params;
+use rpassword::prompt_password;
+use tracing::{debug, info};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -24,7 +58,7 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Craft an item locally
+    /// Craft an item locally.  An interrupted craft is resumed when run again with the same output
     Craft {
         #[arg(long, value_name = "RECIPE")]
         recipe: String,
@@ -37,22 +71,131 @@ enum Commands {
     Commit {
         #[arg(long, value_name = "FILE")]
         input: PathBuf,
+        /// Wait until the synchronizer accepts or rejects the item
+        #[arg(long)]
+        wait: bool,
+        /// Maximum time to wait, in seconds
+        #[arg(long, value_name = "SECONDS", default_value_t = 1800)]
+        timeout: u64,
+    },
+    /// Wait until the synchronizer accepts or rejects a committed item
+    Status {
+        #[arg(long, value_name = "FILE")]
+        input: PathBuf,
+        /// Maximum time to wait, in seconds
+        #[arg(long, value_name = "SECONDS", default_value_t = 1800)]
+        timeout: u64,
+    },
+    /// Destroy a committed item on-chain, so that it can't be consumed anymore
+    Destroy {
+        #[arg(long, value_name = "FILE")]
+        input: PathBuf,
+    },
+    /// Write a request to receive an item from another player, keeping its key next to it
+    TransferRequest {
+        #[arg(long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Transfer a committed item on-chain to the author of a transfer request
+    Transfer {
+        #[arg(long, value_name = "FILE")]
+        input: PathBuf,
+        #[arg(long, value_name = "FILE")]
+        request: PathBuf,
+        /// Offer to send to the recipient
+        #[arg(long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Claim an item transferred in response to a transfer request
+    Claim {
+        #[arg(long, value_name = "FILE")]
+        offer: PathBuf,
+        #[arg(long, value_name = "FILE")]
+        request: PathBuf,
+        #[arg(long, value_name = "FILE")]
+        output: PathBuf,
     },
-    /// Verify a committed item
+    /// Verify a committed item and that it hasn't been consumed
     Verify {
         #[arg(long, value_name = "FILE")]
         input: PathBuf,
     },
+    /// Explain why the synchronizer rejected the payloads of a commit transaction
+    Rejections {
+        #[arg(long, value_name = "TX_HASH")]
+        tx: TxHash,
+    },
+    /// Rebuild the missing items crafted or claimed with keys derived from the master seed
+    Recover,
+    /// Create the wallet holding the Ethereum private key and the master seed, if it doesn't exist
+    InitWallet,
+    /// Unlock the wallet for the following commands, keeping its secrets in a background agent
+    Unlock {
+        /// Time after which the wallet is locked again, in seconds
+        #[arg(long, value_name = "SECONDS", default_value_t = 3600)]
+        timeout: u64,
+    },
+    /// Lock the wallet, stopping its agent
+    Lock,
+    /// Agent holding the secrets of the unlocked wallet, started by `unlock`
+    #[command(hide = true)]
+    Agent {
+        #[arg(long, value_name = "SECONDS")]
+        timeout: u64,
+    },
+}
+
+impl Commands {
+    /// Whether the command uses the secrets of the wallet, which then asks for its password
+    /// unless it is unlocked.  The commands only reading the identifiers of the items don't.
+    fn uses_secrets(&self) -> bool {
+        !matches!(
+            self,
+            Commands::Status { .. }
+                | Commands::Verify { .. }
+                | Commands::Rejections { .. }
+                | Commands::InitWallet
+                | Commands::Unlock { .. }
+                | Commands::Lock
+                | Commands::Agent { .. }
+        )
+    }
+}
+
+/// Password of the wallet, read from the file at WALLET_PASSWORD_FILE if set, or asked for with
+/// `prompt` otherwise.
+fn wallet_password(prompt: &str) -> Result<String> {
+    match dotenvy::var("WALLET_PASSWORD_FILE") {
+        Ok(path) if !path.is_empty() => Ok(fs::read_to_string(&path)?
+            .trim_end_matches(['\r', '\n'])
+            .to_string()),
+        _ => Ok(prompt_password(prompt)?),
+    }
+}
+
+/// Secrets of the wallet at `wallet`, from its agent if it is unlocked.  They are only kept in
+/// memory for this command.
+fn wallet_secrets(wallet: &Path) -> Result<Secrets> {
+    match agent_secrets(wallet)? {
+        Some(secrets) => Ok(secrets),
+        None => open_wallet(
+            wallet,
+            &wallet_password(&format!("Password of the wallet at {}: ", wallet.display()))?,
+        ),
+    }
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     log_init();
     load_dotenv()?;
-    let cfg = Config::from_env()?;
+    let mut cfg = Config::from_env()?;
     info!(?cfg, "Loaded config");
+    if cli.command.as_ref().is_some_and(Commands::uses_secrets) {
+        cfg.secrets = Some(wallet_secrets(Path::new(&cfg.wallet_path))?);
+    }
 
     let params = Params::default();
 
@@ -63,44 +206,113 @@ async fn main() -> anyhow::Result<()> {
             inputs,
         }) => {
             let recipe = Recipe::from_str(&recipe)?;
-            craft_item(&params, recipe, &output, &inputs)?;
+            let mut keychain = cfg.keychain()?;
+            craft_item(&params, &mut keychain, recipe, &output, &inputs, &|tried| {
+                debug!("Mining: {tried} seeds tried")
+            })?;
         }
-        Some(Commands::Commit { input }) => {
+        Some(Commands::Commit {
+            input,
+            wait,
+            timeout,
+        }) => {
             commit_item(&params, &cfg, &input).await?;
+            if wait {
+                let status =
+                    wait_commit(&params, &cfg, &input, Duration::from_secs(timeout)).await?;
+                info!("Commit status of {input:?}: {status:?}");
+            }
+        }
+        Some(Commands::Status { input, timeout }) => {
+            if let Some(status) = load_commit_status(&input)? {
+                info!("Commit status of {input:?}: {status:?}");
+            }
+            let status = wait_commit(&params, &cfg, &input, Duration::from_secs(timeout)).await?;
+            info!("Commit status of {input:?}: {status:?}");
+        }
+        Some(Commands::Destroy { input }) => {
+            destroy_item(&params, &cfg, &input).await?;
+        }
+        Some(Commands::TransferRequest { output }) => {
+            request_transfer(&mut cfg.keychain()?, &output)?;
+        }
+        Some(Commands::Transfer {
+            input,
+            request,
+            output,
+        }) => {
+            transfer_item(&params, &cfg, &input, &request, &output).await?;
+        }
+        Some(Commands::Claim {
+            offer,
+            request,
+            output,
+        }) => {
+            claim_item(&params, &cfg, &offer, &request, &output).await?;
+            info!("Claimed item stored at {output:?}");
         }
         Some(Commands::Verify { input }) => {
-            let crafted_item = load_item(&input)?;
-
-            // Verify that the item exists on-blob-space:
-            // first get the merkle proof of item existence from the Synchronizer
-            let item = RawValue::from(crafted_item.def.item_hash(&params)?);
-            let item_hex: String = format!("{item:#}");
-            let (epoch, _): (u64, RawValue) =
-                reqwest::blocking::get(format!("{}/created_items_root", cfg.sync_url,))?.json()?;
-            info!("Verifying commitment of item {item:#} via synchronizer at epoch {epoch}");
-            let (epoch, mtp): (u64, MerkleProof) = reqwest::blocking::get(format!(
-                "{}/created_item/{}",
-                cfg.sync_url,
-                &item_hex[2..]
-            ))?
-            .json()?;
-            info!("mtp at epoch {epoch}: {mtp:?}");
-
-            // fetch the associated Merkle root
-            let merkle_root: RawValue =
-                reqwest::blocking::get(format!("{}/created_items_root/{}", cfg.sync_url, &epoch))?
-                    .json()?;
-
-            // verify the obtained merkle proof
-            Set::verify(
-                params.max_depth_mt_containers,
-                merkle_root.into(),
-                &mtp,
-                &item.into(),
-            )?;
-
+            verify_item(&params, &cfg, &load_public_item(&input)?).await?;
             info!("Crafted item at {input:?} successfully verified!");
         }
+        Some(Commands::Rejections { tx }) => {
+            let rejections = cfg.sync_client(&params)?.rejections(&tx).await?;
+            if rejections.is_empty() {
+                info!("No rejected payloads in tx {tx}");
+            }
+            for rejection in rejections {
+                info!(
+                    "Rejected payload at slot {}, blob_index {}, payload_index {:?}: {}: {}",
+                    rejection.slot,
+                    rejection.blob_index,
+                    rejection.payload_index,
+                    rejection.reason,
+                    rejection.message
+                );
+            }
+        }
+        Some(Commands::Recover) => {
+            let mut keychain = cfg.keychain()?;
+            let recovered = recover_items(&params, &mut keychain, &|tried| {
+                debug!("Mining: {tried} seeds tried")
+            })?;
+            info!("Recovered {} items: {recovered:?}", recovered.len());
+        }
+        Some(Commands::InitWallet) => {
+            let wallet_path = Path::new(&cfg.wallet_path);
+            if wallet_path.exists() {
+                info!("Wallet at {wallet_path:?} already exists");
+                return Ok(());
+            }
+            let password = wallet_password("New wallet password: ")?;
+            if password != wallet_password("Repeat the wallet password: ")? {
+                bail!("The passwords don't match");
+            }
+            // Import the private key from the plaintext config of previous versions
+            let priv_key = match dotenvy::var("PRIV_KEY") {
+                Ok(priv_key) => {
+                    info!("Importing PRIV_KEY into the wallet, remove it from the .env file");
+                    priv_key
+                }
+                Err(_) => prompt_password("Ethereum private key (empty to mock txs): ")?,
+            };
+            create_wallet(wallet_path, &password, &Secrets::new(priv_key))?;
+        }
+        Some(Commands::Unlock { timeout }) => {
+            let wallet_path = Path::new(&cfg.wallet_path);
+            let secrets = open_wallet(wallet_path, &wallet_password("Wallet password: ")?)?;
+            start_agent(wallet_path, &secrets, Duration::from_secs(timeout))?;
+        }
+        Some(Commands::Lock) => {
+            if stop_agent(Path::new(&cfg.wallet_path))? {
+                info!("Locked wallet at {}", cfg.wallet_path);
+            } else {
+                info!("Wallet at {} is not unlocked", cfg.wallet_path);
+            }
+        }
+        Some(Commands::Agent { timeout }) => {
+            run_agent(Path::new(&cfg.wallet_path), Duration::from_secs(timeout))?;
+        }
         None => {}
     }
 
@@ -0,0 +1,179 @@
+//! Transfer of an item to another player.  The recipient writes a request holding the commitment
+//! to a key that only they know, the sender consumes the item on-chain creating a successor bound
+//! to that commitment, and the recipient claims the successor by proving knowledge of the key.
+//! The sender also proves that the successor follows the recipe of the item, so that the
+//! recipient can craft with it.
+
+use std::{fs, path::Path};
+
+use alloy::primitives::TxHash;
+use anyhow::{Result, bail};
+use commitlib::{ItemDef, key_commitment};
+use common::{
+    payload::{Payload, PayloadKind},
+    set_from_value,
+};
+use pod2::{
+    frontend::MainPod,
+    middleware::{DEFAULT_VD_SET, Hash, Params, RawValue, Value},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::info;
+
+use crate::{
+    Config, CraftedItem, Helper,
+    commit::{CommitStatus, store_commit_status},
+    created_items::sync_created_items,
+    eth::send_payload,
+    keychain::Keychain,
+    load_item, move_to_used, shrink_payload_proof, store_item, used_item_path,
+};
+
+/// Request to receive an item, sent by the recipient to the sender
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferRequest {
+    pub key_commitment: Hash,
+}
+
+/// Item transferred by the sender, sent back to the recipient to claim it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferOffer {
+    pub def: ItemDef,
+    // Pod proving the app layer predicate of the transferred item
+    pub recipe_pod: MainPod,
+    pub tx_hash: TxHash,
+}
+
+pub(crate) fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+/// Writes a transfer request to `output`, committing to a new key of `keychain`.  The request is
+/// meant to be sent to the sender.
+pub fn request_transfer(keychain: &mut Keychain, output: &Path) -> Result<()> {
+    // The key is recorded first so that a request can always be claimed
+    let (_, key) = keychain.new_request_key(output)?;
+    let request = TransferRequest {
+        key_commitment: key_commitment(key),
+    };
+    fs::write(output, serde_json::to_vec_pretty(&request)?)?;
+    info!(
+        "Stored transfer request with key commitment {:#} to {output:?}",
+        request.key_commitment
+    );
+    Ok(())
+}
+
+/// Transfers the item at `input` on-chain to the author of the request at `request`, then
+/// moves it into the `used` subdirectory and writes the offer for the recipient to `output`.
+/// Returns the hash of the transaction carrying the payload.
+pub async fn transfer_item(
+    params: &Params,
+    cfg: &Config,
+    input: &Path,
+    request: &Path,
+    output: &Path,
+) -> Result<TxHash> {
+    let used_item = used_item_path(input)?;
+    let crafted_item = load_item(&cfg.file_cipher()?, input)?;
+    let request: TransferRequest = read_json(request)?;
+    let created_items = sync_created_items(params, cfg).await?;
+
+    let helper = Helper::new(params.clone(), DEFAULT_VD_SET.clone());
+    let pod = helper.make_transfer_pod(
+        crafted_item.clone(),
+        request.key_commitment,
+        created_items.clone(),
+    )?;
+    let recipe_pod =
+        helper.make_transferred_recipe_pod(crafted_item.clone(), request.key_commitment)?;
+
+    let st_commit_transfer = pod.public_statements[0].clone();
+    let item = st_commit_transfer.args()[0].literal()?.raw();
+    let nullifier_set = set_from_value(&st_commit_transfer.args()[1].literal()?)?;
+    let nullifiers: Vec<RawValue> = nullifier_set.set().iter().map(|v| v.raw()).collect();
+    let payload_bytes = Payload {
+        proof: shrink_payload_proof(params, pod)?,
+        kind: PayloadKind::Transfer { item },
+        created_items_root: RawValue::from(created_items.commitment()),
+        nullifiers,
+    }
+    .to_bytes();
+
+    let tx_hash = send_payload(cfg, payload_bytes).await?;
+    info!("Transferred item as {item:#} in tx={tx_hash}");
+
+    let offer = TransferOffer {
+        def: crafted_item.def.transferred(request.key_commitment),
+        recipe_pod,
+        tx_hash,
+    };
+    fs::write(output, serde_json::to_vec_pretty(&offer)?)?;
+    info!("Stored transfer offer to {output:?}");
+
+    move_to_used(input, &used_item)?;
+
+    Ok(tx_hash)
+}
+
+/// Claims the item of the offer at `offer`, made for the request at `request`, once the
+/// synchronizer has accepted its transfer.  Stores the item at `output` and returns the epoch
+/// at which it was created.
+pub async fn claim_item(
+    params: &Params,
+    cfg: &Config,
+    offer: &Path,
+    request: &Path,
+    output: &Path,
+) -> Result<u64> {
+    let offer_path = offer;
+    let offer: TransferOffer = read_json(offer)?;
+    let request_key_commitment = read_json::<TransferRequest>(request)?.key_commitment;
+    let mut keychain = cfg.keychain()?;
+    let Some((key_index, key)) = keychain.request_key(request_key_commitment) else {
+        bail!("The request at {request:?} was not made with this keychain");
+    };
+    if offer.def.key_commitment() != Some(&Value::from(request_key_commitment)) {
+        bail!("The offer is not bound to the request");
+    }
+
+    let item = RawValue::from(offer.def.item_hash(params)?);
+    let client = cfg.sync_client(params)?;
+    let Some((epoch, update)) = client.item_provenance(&item).await? else {
+        bail!(
+            "Item {item:#} has not been created yet, check the transfer tx={}",
+            offer.tx_hash
+        );
+    };
+    client.verify_created_item(&item).await?;
+    info!("Verified transfer of item {item:#} at epoch {epoch}");
+
+    let crafted_item = claimed_item(params, offer, key)?;
+    store_item(&keychain.file_cipher(), output, &crafted_item)?;
+    keychain.set_claimed(key_index, offer_path, output, crafted_item.pod.item())?;
+    store_commit_status(
+        output,
+        &CommitStatus::Accepted {
+            tx_hash: update.provenance.tx_hash,
+            epoch,
+        },
+    )?;
+    info!("Stored claimed item to {output:?}");
+
+    Ok(epoch)
+}
+
+/// Proves the ownership of the item of `offer` with the key of its request.
+pub(crate) fn claimed_item(
+    params: &Params,
+    offer: TransferOffer,
+    key: RawValue,
+) -> Result<CraftedItem> {
+    let helper = Helper::new(params.clone(), DEFAULT_VD_SET.clone());
+    let pod = helper.make_transferred_item_pod(offer.def.clone(), key, offer.recipe_pod)?;
+    Ok(CraftedItem {
+        pod,
+        def: offer.def,
+        transfer_key: Some(key),
+    })
+}
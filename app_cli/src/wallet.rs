@@ -0,0 +1,235 @@
+//! Encrypted wallet holding the player's secrets: the Ethereum private key used to send the
+//! commit transactions, and the master seed the item keys are derived from.  The secrets are
+//! encrypted with ChaCha20-Poly1305 under a key derived from the password with Argon2.
+//!
+//! The decrypted secrets are only ever kept in memory, by the command that asked for the password
+//! or by the agent of the unlocked wallet (see [`crate::agent`]), never written to the disk.  The
+//! item keys stored under PODS_PATH are encrypted with a [`FileCipher`] keyed by the master seed,
+//! so they are only readable with the unlocked wallet too.
+
+use std::{fmt, fs, io::Write, path::Path};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use argon2::Argon2;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce, aead::Aead};
+use plonky2::field::types::PrimeField64;
+use pod2::middleware::{RawValue, Value, hash_values};
+use rand::{RngCore, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::info;
+
+use crate::rand_raw_value;
+
+/// Domain separator of the derivation of the key encrypting the item files
+pub const FILE_KEY_DERIVATION: &str = "file key derivation";
+
+const WALLET_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Secrets {
+    /// Ethereum private key to send txs, empty to only mock them
+    pub priv_key: String,
+    /// Master seed the item keys are derived from
+    pub seed: RawValue,
+}
+
+impl Secrets {
+    /// Secrets with `priv_key` and a new random master seed
+    pub fn new(priv_key: String) -> Self {
+        Self {
+            priv_key,
+            seed: rand_raw_value(),
+        }
+    }
+}
+
+// Never print the secrets, as the config holding them is logged
+impl fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secrets { .. }")
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct WalletFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Value encrypted with a [`FileCipher`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Encrypted {
+    nonce: String,
+    ciphertext: String,
+}
+
+fn cipher(password: &str, salt: &[u8]) -> Result<ChaCha20Poly1305> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive the wallet key: {e}"))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Writes `contents` to `path` through a temporary file only readable by the user.
+pub(crate) fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&tmp_path)?.write_all(contents)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+/// Creates the wallet at `path` holding `secrets` encrypted under `password`.
+pub fn create_wallet(path: &Path, password: &str, secrets: &Secrets) -> Result<()> {
+    if path.exists() {
+        bail!("Wallet at {path:?} already exists");
+    }
+    let mut rng = StdRng::from_os_rng();
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut nonce);
+    let ciphertext = cipher(password, &salt)?
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            serde_json::to_vec(secrets)?.as_slice(),
+        )
+        .map_err(|_| anyhow!("Failed to encrypt the wallet"))?;
+    let wallet = WalletFile {
+        version: WALLET_VERSION,
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    };
+    write_private(path, &serde_json::to_vec_pretty(&wallet)?)?;
+    info!("Created wallet at {path:?}");
+    Ok(())
+}
+
+/// Decrypts the secrets of the wallet at `path` with `password`.
+pub fn open_wallet(path: &Path, password: &str) -> Result<Secrets> {
+    let wallet: WalletFile = serde_json::from_slice(&fs::read(path)?)?;
+    if wallet.version != WALLET_VERSION {
+        bail!("Unsupported wallet version {}", wallet.version);
+    }
+    let nonce = hex::decode(&wallet.nonce)?;
+    if nonce.len() != NONCE_LEN {
+        bail!("Invalid wallet nonce");
+    }
+    let plaintext = cipher(password, &hex::decode(&wallet.salt)?)?
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            hex::decode(&wallet.ciphertext)?.as_slice(),
+        )
+        .map_err(|_| anyhow!("Wrong password for the wallet at {path:?}"))?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// Cipher of the secrets stored under PODS_PATH: the items and their craft checkpoints.
+#[derive(Clone)]
+pub struct FileCipher(ChaCha20Poly1305);
+
+impl FileCipher {
+    /// Cipher keyed by the master seed
+    pub fn new(seed: RawValue) -> Self {
+        let key = RawValue::from(hash_values(&[
+            Value::from(seed),
+            Value::from(FILE_KEY_DERIVATION),
+        ]));
+        let key: Vec<u8> = key
+            .0
+            .iter()
+            .flat_map(|f| f.to_canonical_u64().to_le_bytes())
+            .collect();
+        Self(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    pub fn encrypt<T: Serialize>(&self, value: &T) -> Result<Encrypted> {
+        let mut nonce = [0u8; NONCE_LEN];
+        StdRng::from_os_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .0
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                serde_json::to_vec(value)?.as_slice(),
+            )
+            .map_err(|_| anyhow!("Failed to encrypt"))?;
+        Ok(Encrypted {
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    pub fn decrypt<T: DeserializeOwned>(&self, encrypted: &Encrypted) -> Result<T> {
+        let nonce = hex::decode(&encrypted.nonce)?;
+        if nonce.len() != NONCE_LEN {
+            bail!("Invalid nonce");
+        }
+        let plaintext = self
+            .0
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                hex::decode(&encrypted.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| anyhow!("Failed to decrypt, the value belongs to another wallet"))?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Writes `value` encrypted to `path`.
+    pub fn write<T: Serialize>(&self, path: &Path, value: &T) -> Result<()> {
+        write_private(path, &serde_json::to_vec(&self.encrypt(value)?)?)
+    }
+
+    /// Reads the value written to `path` with `write`.
+    pub fn read<T: DeserializeOwned>(&self, path: &Path) -> Result<T> {
+        let encrypted: Encrypted = serde_json::from_slice(&fs::read(path)?)?;
+        self.decrypt(&encrypted)
+            .with_context(|| format!("Failed to read {path:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wallet() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("wallet-test-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("wallet.json");
+        let secrets = Secrets {
+            priv_key: "bcdf20249abf0ed6d944c0288fad489e33f66b3960d9e6229c1cd214ed3bbe31".into(),
+            seed: RawValue::from(42),
+        };
+
+        create_wallet(&path, "correct horse", &secrets)?;
+        assert!(create_wallet(&path, "correct horse", &secrets).is_err());
+        assert!(open_wallet(&path, "wrong horse").is_err());
+        let opened = open_wallet(&path, "correct horse")?;
+        assert_eq!(opened.priv_key, secrets.priv_key);
+        assert_eq!(opened.seed, secrets.seed);
+        assert!(!fs::read_to_string(&path)?.contains(&secrets.priv_key));
+
+        let file = dir.join("item");
+        FileCipher::new(secrets.seed).write(&file, &secrets.priv_key)?;
+        assert!(!fs::read_to_string(&file)?.contains(&secrets.priv_key));
+        let read: String = FileCipher::new(secrets.seed).read(&file)?;
+        assert_eq!(read, secrets.priv_key);
+        assert!(
+            FileCipher::new(RawValue::from(43))
+                .read::<String>(&file)
+                .is_err()
+        );
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -9,7 +9,10 @@ use std::{
 };
 
 use anyhow::Result;
-use app_cli::{Config, CraftedItem, USED_ITEM_SUBDIR_NAME, load_item};
+use app_cli::{
+    Config, CraftedItem, USED_ITEM_SUBDIR_NAME, checkpoint::is_craft_checkpoint_path,
+    commit::is_commit_status_path, load_item, wallet::open_wallet,
+};
 use pod2::middleware::{Hash, Params};
 use tracing::error;
 
@@ -41,6 +44,8 @@ pub struct App {
     pub danger: bool,
     pub cute: bool,
     pub show_cheats: bool,
+    pub wallet_password: String,
+    pub wallet_error: Option<String>,
 }
 
 impl App {
@@ -81,7 +86,7 @@ IsTinPremium(item, private: ingredients, inputs, key, work) = AND(
 )"#
         .into();
 
-        let mut app = Self {
+        let app = Self {
             cfg,
             params,
             items: vec![],
@@ -99,11 +104,55 @@ IsTinPremium(item, private: ingredients, inputs, key, work) = AND(
             danger: false,
             cute: false,
             show_cheats: false,
+            wallet_password: String::new(),
+            wallet_error: None,
         };
-        app.refresh_items()?;
+        // The items are loaded once the wallet is unlocked, as their files are encrypted
         Ok(app)
     }
 
+    /// Asks for the password of the wallet, whose secrets are kept in memory once unlocked.
+    pub fn update_unlock_ui(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                let wallet_path = Path::new(&self.cfg.wallet_path);
+                if !wallet_path.exists() {
+                    ui.heading("No wallet");
+                    ui.label(format!(
+                        "Create the wallet at {} with `cargo run --release -p app_cli -- init-wallet`",
+                        wallet_path.display()
+                    ));
+                    return;
+                }
+                ui.heading("Unlock wallet");
+                let password = ui.add(
+                    egui::TextEdit::singleline(&mut self.wallet_password)
+                        .password(true)
+                        .hint_text("Password"),
+                );
+                let submitted =
+                    password.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("Unlock").clicked() || submitted {
+                    match open_wallet(wallet_path, &self.wallet_password) {
+                        Ok(secrets) => {
+                            self.cfg.secrets = Some(secrets);
+                            self.wallet_error = None;
+                            if let Err(e) = self.refresh_items() {
+                                self.cfg.secrets = None;
+                                self.wallet_error = Some(e.to_string());
+                            }
+                        }
+                        Err(e) => self.wallet_error = Some(e.to_string()),
+                    }
+                    self.wallet_password.clear();
+                }
+                if let Some(e) = &self.wallet_error {
+                    ui.colored_label(egui::Color32::LIGHT_RED, e);
+                }
+            });
+        });
+    }
+
     /// returns a vector with [self.items | self.used_items]
     pub fn all_items(&self) -> Vec<Item> {
         [self.items.clone(), self.used_items.clone()].concat()
@@ -112,13 +161,8 @@ IsTinPremium(item, private: ingredients, inputs, key, work) = AND(
     pub fn load_item(&mut self, entry: &Path, used: bool) -> Result<()> {
         log::debug!("loading {entry:?}");
         let name = entry.file_name().unwrap().to_str().unwrap().to_string();
-        let crafted_item = load_item(entry)?;
-        let id = Hash::from(
-            crafted_item.pod.public_statements[0].args()[0]
-                .literal()
-                .unwrap()
-                .raw(),
-        );
+        let crafted_item = load_item(&self.cfg.file_cipher()?, entry)?;
+        let id = crafted_item.pod.item();
         let item = Item {
             name,
             id,
@@ -144,8 +188,11 @@ IsTinPremium(item, private: ingredients, inputs, key, work) = AND(
         log::info!("Loading items...");
         for entry in fs::read_dir(&self.cfg.pods_path)? {
             let entry = entry?;
-            // skip dirs
-            if !entry.file_type()?.is_dir() {
+            // skip dirs, commit status files and checkpoints of interrupted crafts
+            if !entry.file_type()?.is_dir()
+                && !is_commit_status_path(&entry.path())
+                && !is_craft_checkpoint_path(&entry.path())
+            {
                 self.load_item(&(entry.path()), false)?;
             }
         }
@@ -153,8 +200,8 @@ IsTinPremium(item, private: ingredients, inputs, key, work) = AND(
         log::info!("Loading used items...");
         for entry in fs::read_dir(format!("{}/{}", &self.cfg.pods_path, USED_ITEM_SUBDIR_NAME))? {
             let entry = entry?;
-            // skip dirs
-            if !entry.file_type()?.is_dir() {
+            // skip dirs and commit status files
+            if !entry.file_type()?.is_dir() && !is_commit_status_path(&entry.path()) {
                 self.load_item(&(entry.path()), true)?;
             }
         }
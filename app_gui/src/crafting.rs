@@ -4,7 +4,7 @@ use std::{
 };
 
 use anyhow::{Result, anyhow};
-use app_cli::Recipe;
+use app_cli::{ProductionType, RECIPES, Recipe};
 use egui::{Frame, ImageSource, Label, RichText, Ui};
 use enum_iterator::{Sequence, all};
 use lazy_static::lazy_static;
@@ -16,10 +16,8 @@ const INDENTATION: &str = "    ";
 
 #[derive(Debug, Clone, Copy, PartialEq, IntoStaticStr)]
 pub enum Process {
-    Stone,
-    Wood,
-    Axe,
-    WoodenAxe,
+    Recipe(Recipe),
+    Destroy,
     Mock(&'static str),
 }
 
@@ -35,84 +33,41 @@ pub struct ProcessData {
 }
 
 lazy_static! {
-    static ref STONE_DATA: ProcessData = ProcessData {
-        description: "Stone.  Hard to find.",
-        outputs: &["Stone"],
-        predicate: r#"
-use intro Pow(count, input, output) from 0x3493488bc23af15ac5fabe38c3cb6c4b66adb57e3898adf201ae50cc57183f65
-
-IsStone(item, private: ingredients, inputs, key, work) = AND(
-    ItemDef(item, ingredients, inputs, key, work)
-    Equal(inputs, {})
-    DictContains(ingredients, "blueprint", "stone")
-    Pow(3, ingredients, work)
-)"#,
-        ..Default::default()
-    };
-    static ref WOOD_DATA: ProcessData = ProcessData {
-        description: "Wood.  Easily available.",
-        outputs: &["Wood"],
-        predicate: r#"
-IsWood(item, private: ingredients, inputs, key, work) = AND(
-    ItemDef(item, ingredients, inputs, key, work)
-    Equal(inputs, {})
-    DictContains(ingredients, "blueprint", "wood")
-)"#,
-        ..Default::default()
-    };
-    static ref AXE_DATA: ProcessData = ProcessData {
-        description: "Axe.  Easy to craft.",
-        input_ingredients: &["Wood", "Stone"],
-        outputs: &["Axe"],
-        predicate: r#"
-IsAxe(item, private: ingredients, inputs, key, work, s1, wood, stone) = AND(
-    ItemDef(item, ingredients, inputs, key, work)
-    DictContains(ingredients, "blueprint", "axe")
-    Equal(work, {})
-
-    // 2 ingredients
-    SetInsert(s1, {}, wood)
-    SetInsert(inputs, s1, stone)
-
-    // prove the ingredients are correct.
-    IsWood(wood)
-    IsStone(stone)
-)"#,
-        ..Default::default()
-    };
-    static ref WOODEN_AXE_DATA: ProcessData = ProcessData {
-        description: "Wooden Axe.  Easy to craft.",
-        input_ingredients: &["Wood", "Wood"],
-        outputs: &["WoodenAxe"],
-        predicate: r#"
-IsWoodenAxe(item, private: ingredients, inputs, key, work, s1, wood1, wood2) = AND(
-    ItemDef(item, ingredients, inputs, key, work)
-    DictContains(ingredients, "blueprint", "wooden-axe")
-    Equal(work, 0)
-
-    // 2 ingredients
-    SetInsert(s1, {}, wood1)
-    SetInsert(inputs, s1, wood2)
-
-    // prove the ingredients are correct.
-    IsWood(wood1)
-    IsWood(wood2)
-)"#,
-        ..Default::default()
-    };
-    // Mock
+    // Keyed by blueprint, generated from the recipe registry
+    static ref RECIPE_DATA: HashMap<&'static str, ProcessData> = RECIPES
+        .recipes()
+        .iter()
+        .map(|recipe| {
+            let input_ingredients = recipe
+                .inputs
+                .iter()
+                .map(|input| RECIPES.get(input).expect("validated input").name.as_str())
+                .collect::<Vec<_>>();
+            let data = ProcessData {
+                description: &recipe.description,
+                input_ingredients: input_ingredients.leak(),
+                outputs: vec![recipe.name.as_str()].leak(),
+                predicate: recipe.podlang(&RECIPES).join("\n\n").leak(),
+                ..Default::default()
+            };
+            (recipe.blueprint.as_str(), data)
+        })
+        .collect();
     static ref DESTROY_DATA: ProcessData = ProcessData {
-        description: "Destroy an object.",
+        description: "Destroy an object.  Its nullifier is published on-chain so that it can't be used anymore.",
         input_ingredients: &["Item to destroy"],
         outputs: &[],
         predicate: r#"
-Destroy(batch, private: ingredients, inputs, key, work, item) = AND(
-    BatchDef(batch, ingredients, inputs, key, work)
-    Equal(batch, {})
-    SetInsert(inputs, {}, item)
+CommitDestruction(nullifiers, created_items, private: inputs) = AND(
+    // Prove all inputs are in the created set
+    SubsetOf(inputs, created_items)
+
+    // Expose nullifiers for all inputs
+    Nullifiers(nullifiers, inputs)
 )"#,
         ..Default::default()
     };
+    // Mock
     static ref TOMATO_DATA: ProcessData = ProcessData {
         description: "Produces a Tomato.  Requires farm level 1.",
         input_facilities: &["Farm level 1"],
@@ -390,27 +345,27 @@ impl Process {
                 let s = s.strip_prefix("Reconfigure-").unwrap_or(s);
                 s
             }
+            Self::Recipe(recipe) => recipe.blueprint().name.as_str(),
             v => v.into(),
         }
     }
     // Returns None if the Process is mock
     pub fn recipe(&self) -> Option<Recipe> {
         match self {
-            Self::Stone => Some(Recipe::Stone),
-            Self::Wood => Some(Recipe::Wood),
-            Self::Axe => Some(Recipe::Axe),
-            Self::WoodenAxe => Some(Recipe::WoodenAxe),
+            Self::Recipe(recipe) => Some(*recipe),
+            Self::Destroy => None,
             Self::Mock(_) => None,
         }
     }
 
+    pub fn is_mock(&self) -> bool {
+        matches!(self, Self::Mock(_))
+    }
+
     pub fn data(&self) -> &'static ProcessData {
         match self {
-            Self::Stone => &STONE_DATA,
-            Self::Wood => &WOOD_DATA,
-            Self::Axe => &AXE_DATA,
-            Self::WoodenAxe => &WOODEN_AXE_DATA,
-            Self::Mock("Destroy") => &DESTROY_DATA,
+            Self::Recipe(recipe) => &RECIPE_DATA[recipe.blueprint().blueprint.as_str()],
+            Self::Destroy => &DESTROY_DATA,
             Self::Mock("Tomato") => &TOMATO_DATA,
             Self::Mock("Steel Sword") => &STEEL_SWORD_DATA,
             Self::Mock("Disassemble-H2O") => &DIS_H2O_DATA,
@@ -449,9 +404,15 @@ impl Verb {
 
     pub fn processes(&self) -> Vec<Process> {
         use Process::*;
+        let recipes = |production_type: ProductionType| {
+            Recipe::list()
+                .into_iter()
+                .filter(move |recipe| recipe.production_type() == production_type)
+                .map(Process::Recipe)
+        };
         match self {
             Self::Mine => vec![Mock("Coal")],
-            Self::Gather => vec![Stone, Wood],
+            Self::Gather => recipes(ProductionType::Mine).collect(),
             Self::Refine => vec![Mock("Refine-Uranium")],
             Self::Reconfigure => vec![
                 Mock("Reconfigure-Rubik's Cube"),
@@ -459,16 +420,18 @@ impl Verb {
                 Mock("Reconfigure-Refrigerator"),
                 Mock("Reconfigure-Farm Level 1"),
             ],
-            Self::Craft => vec![Axe, WoodenAxe, Mock("Tree House")],
+            Self::Craft => recipes(ProductionType::Craft)
+                .chain([Mock("Tree House")])
+                .collect(),
             Self::Produce => vec![Mock("Tomato"), Mock("Steel Sword")],
             Self::Disassemble => vec![Mock("Disassemble-H2O")],
-            Self::Destroy => vec![Mock("Destroy")],
+            Self::Destroy => vec![Process::Destroy],
         }
     }
 
     pub fn default_process(&self) -> Option<Process> {
         match self {
-            Self::Destroy => Some(Process::Mock("Destroy")),
+            Self::Destroy => Some(Process::Destroy),
             _ => None,
         }
     }
@@ -529,7 +492,7 @@ impl App {
                         for process in selected_verb
                             .processes()
                             .into_iter()
-                            .filter(|p| self.mock_mode || (p.recipe().is_some()))
+                            .filter(|p| self.mock_mode || !p.is_mock())
                         {
                             ui.selectable_value(
                                 &mut selected_process,
@@ -541,7 +504,7 @@ impl App {
             }
             if let Some(process) = selected_process {
                 self.crafting.select(process);
-                if process.recipe().is_none() {
+                if process.is_mock() {
                     ui.colored_label(egui::Color32::from_rgb(81, 77, 188), "(mock)");
                 }
             }
@@ -680,8 +643,11 @@ impl App {
             // NOTE: If we don't show filenames in the left panel, then we shouldn't ask for a
             // filename either.
             if self.crafting.output_filename.is_empty() {
-                self.crafting.output_filename =
-                    format!("{:?}_{}", process, self.items.len() + self.used_items.len());
+                self.crafting.output_filename = format!(
+                    "{}_{}",
+                    process.as_str(),
+                    self.items.len() + self.used_items.len()
+                );
             }
 
             ui.add_space(8.0);
@@ -710,6 +676,27 @@ impl App {
                         });
                 });
 
+            if process == Process::Destroy {
+                if button_commit_clicked || button_craft_and_commit_clicked {
+                    match self.crafting.input_items.get(&0) {
+                        None => {
+                            self.crafting.commit_result =
+                                Some(Err(anyhow!("Please provide all inputs.")))
+                        }
+                        Some(index) => {
+                            self.task_req_tx
+                                .send(Request::Destroy {
+                                    params: self.params.clone(),
+                                    cfg: self.cfg.clone(),
+                                    input: self.all_items()[*index].path.clone(),
+                                })
+                                .unwrap();
+                        }
+                    }
+                }
+                return;
+            }
+
             if button_craft_clicked {
                 if self.crafting.output_filename.is_empty() {
                     self.crafting.craft_result = Some(Err(anyhow!("Please enter a filename.")));
@@ -736,7 +723,7 @@ impl App {
                                 self.task_req_tx
                                     .send(Request::Craft {
                                         params: self.params.clone(),
-                                        pods_path: self.cfg.pods_path.clone(),
+                                        cfg: self.cfg.clone(),
                                         recipe,
                                         output,
                                         input_paths,
@@ -790,7 +777,6 @@ impl App {
                                     .send(Request::CraftAndCommit {
                                         params: self.params.clone(),
                                         cfg: self.cfg.clone(),
-                                        pods_path: self.cfg.pods_path.clone(),
                                         recipe,
                                         output,
                                         input_paths,
@@ -1,9 +1,7 @@
 use anyhow::Result;
+use app_cli::verify_item;
 use egui::{Frame, Label, RichText, Ui};
-use pod2::{
-    backends::plonky2::primitives::merkletree::MerkleProof,
-    middleware::{RawValue, containers::Set},
-};
+use tokio::runtime::Runtime;
 use tracing::info;
 
 use crate::{
@@ -76,7 +74,7 @@ impl App {
             });
             ui.heading("Statements:");
             egui::ScrollArea::vertical().show(ui, |ui| {
-                let sts = &item.crafted_item.pod.public_statements;
+                let sts = &item.crafted_item.pod.pod().public_statements;
                 ui.separator();
                 Frame::NONE
                     .fill(if ctx.theme() == egui::Theme::Dark {
@@ -105,37 +103,13 @@ impl App {
     }
 
     pub fn verify_item(&self, item: &Item) -> Result<()> {
-        item.crafted_item.pod.pod.verify()?;
-
-        // Verify that the item exists on-blob-space:
-        // first get the merkle proof of item existence from the Synchronizer
-        let item_id = RawValue::from(item.crafted_item.def.item_hash(&self.params)?);
-        let item_hex: String = format!("{item_id:#}");
-        let (epoch, _): (u64, RawValue) =
-            reqwest::blocking::get(format!("{}/created_items_root", self.cfg.sync_url,))?.json()?;
-        info!("Verifying commitment of item {item_id:#} via synchronizer at epoch {epoch}");
-        let (epoch, mtp): (u64, MerkleProof) = reqwest::blocking::get(format!(
-            "{}/created_item/{}",
-            self.cfg.sync_url,
-            &item_hex[2..]
-        ))?
-        .json()?;
-        info!("mtp at epoch {epoch}: {mtp:?}");
-
-        // fetch the associated Merkle root
-        let merkle_root: RawValue = reqwest::blocking::get(format!(
-            "{}/created_items_root/{}",
-            self.cfg.sync_url, &epoch
-        ))?
-        .json()?;
+        item.crafted_item.pod.pod().pod.verify()?;
 
-        // verify the obtained merkle proof
-        Set::verify(
-            self.params.max_depth_mt_containers,
-            merkle_root.into(),
-            &mtp,
-            &item_id.into(),
-        )?;
+        Runtime::new()?.block_on(verify_item(
+            &self.params,
+            &self.cfg,
+            &item.crafted_item.public(),
+        ))?;
 
         info!("Crafted item at {:?} successfully verified!", item.path);
 
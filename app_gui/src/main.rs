@@ -47,6 +47,12 @@ fn main() -> Result<()> {
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Nothing can be crafted nor committed until the wallet is unlocked
+        if self.cfg.secrets.is_none() {
+            self.update_unlock_ui(ctx);
+            return;
+        }
+
         // Process task response messages
         if let Ok(res) = self.task_res_rx.try_recv() {
             match res {
@@ -82,6 +88,14 @@ impl eframe::App for App {
                     self.crafting.craft_result = None;
                     self.crafting.commit_result = Some(r);
                 }
+                Response::Destroy(r) => {
+                    if let Err(e) = &r {
+                        log::error!("{e:?}");
+                    }
+                    self.refresh_items().unwrap();
+                    self.crafting.input_items = HashMap::new();
+                    self.crafting.commit_result = Some(r);
+                }
                 Response::Null => {}
             }
         }
@@ -93,6 +107,12 @@ impl eframe::App for App {
                 if ui.button("Refresh").clicked() {
                     self.refresh_items().unwrap();
                 }
+                if ui.button("Lock").clicked() {
+                    self.cfg.secrets = None;
+                    // The decrypted items hold their keys
+                    self.items.clear();
+                    self.used_items.clear();
+                }
             });
             ui.separator();
             egui::ScrollArea::vertical().show(ui, |ui| {
@@ -137,6 +157,9 @@ impl eframe::App for App {
                         ui.horizontal_centered(|ui| {
                             ui.spinner();
                             ui.heading(task);
+                            if let Some(progress) = task_status.progress {
+                                ui.label(progress);
+                            }
                         });
                     } else {
                         self.update_action_ui(ctx, ui);
@@ -5,19 +5,24 @@ use std::{
 };
 
 use anyhow::{Result, anyhow};
-use app_cli::{Config, Recipe, USED_ITEM_SUBDIR_NAME, commit_item, craft_item};
+use app_cli::{
+    Config, Recipe, USED_ITEM_SUBDIR_NAME, commit::move_commit_status, commit_item, craft_item,
+    destroy_item,
+};
 use pod2::middleware::Params;
 use tokio::runtime::Runtime;
 
 #[derive(Default, Clone)]
 pub struct TaskStatus {
     pub busy: Option<String>,
+    // Progress of the busy task, if it reports any
+    pub progress: Option<String>,
 }
 
 pub enum Request {
     Craft {
         params: Params,
-        pods_path: String,
+        cfg: Config,
         recipe: Recipe,
         output: PathBuf,
         input_paths: Vec<PathBuf>,
@@ -30,11 +35,15 @@ pub enum Request {
     CraftAndCommit {
         params: Params,
         cfg: Config,
-        pods_path: String,
         recipe: Recipe,
         output: PathBuf,
         input_paths: Vec<PathBuf>,
     },
+    Destroy {
+        params: Params,
+        cfg: Config,
+        input: PathBuf,
+    },
     Exit,
 }
 
@@ -42,6 +51,7 @@ pub enum Response {
     Craft(Result<PathBuf>),
     Commit(Result<PathBuf>),
     CraftAndCommit(Result<PathBuf>),
+    Destroy(Result<PathBuf>),
     Null,
 }
 
@@ -53,16 +63,15 @@ pub fn handle_req(task_status: &RwLock<TaskStatus>, req: Request) -> Response {
     match req {
         Request::Craft {
             params,
-            pods_path,
+            cfg,
             recipe,
             output,
             input_paths,
-        } => craft(task_status, &params, pods_path, recipe, output, input_paths),
+        } => craft(task_status, &params, &cfg, recipe, output, input_paths),
         Request::Commit { params, cfg, input } => commit(task_status, &params, cfg, input),
         Request::CraftAndCommit {
             params,
             cfg,
-            pods_path,
             recipe,
             output,
             input_paths,
@@ -70,7 +79,7 @@ pub fn handle_req(task_status: &RwLock<TaskStatus>, req: Request) -> Response {
             if let Response::Craft(Result::Err(e)) = craft(
                 task_status,
                 &params,
-                pods_path,
+                &cfg,
                 recipe,
                 output.clone(),
                 input_paths,
@@ -84,6 +93,7 @@ pub fn handle_req(task_status: &RwLock<TaskStatus>, req: Request) -> Response {
             };
             Response::CraftAndCommit(r)
         }
+        Request::Destroy { params, cfg, input } => destroy(task_status, &params, cfg, input),
         Request::Exit => Response::Null,
     }
 }
@@ -91,7 +101,7 @@ pub fn handle_req(task_status: &RwLock<TaskStatus>, req: Request) -> Response {
 fn craft(
     task_status: &RwLock<TaskStatus>,
     params: &Params,
-    pods_path: String,
+    cfg: &Config,
     recipe: Recipe,
     output: PathBuf,
     input_paths: Vec<PathBuf>,
@@ -99,30 +109,42 @@ fn craft(
     set_busy_task(task_status, "Crafting");
 
     let start = std::time::Instant::now();
-    let r = craft_item(params, recipe, &output, &input_paths);
+    let r = cfg.keychain().and_then(|mut keychain| {
+        craft_item(
+            params,
+            &mut keychain,
+            recipe,
+            &output,
+            &input_paths,
+            &|tried| {
+                task_status.write().unwrap().progress = Some(format!("{tried} seeds mined"));
+            },
+        )
+    });
     log::info!("[TIME] total Craft Item time: {:?}", start.elapsed());
 
-    // move the files of the used inputs into the `used` subdir
-    let used_path = Path::new(&pods_path).join(USED_ITEM_SUBDIR_NAME);
-    for input in input_paths {
-        let parent_path = input.parent().unwrap();
-        // if original file is not in 'used' subdir, move it there, ignore if it already is
-        // in that subdir
-        if parent_path != used_path {
-            fs::rename(
-                input.clone(),
-                format!(
+    // move the files of the used inputs into the `used` subdir, keeping them in place if the
+    // craft failed so that it can be resumed
+    if r.is_ok() {
+        let used_path = Path::new(&cfg.pods_path).join(USED_ITEM_SUBDIR_NAME);
+        for input in input_paths {
+            let parent_path = input.parent().unwrap();
+            // if original file is not in 'used' subdir, move it there, ignore if it already is
+            // in that subdir
+            if parent_path != used_path {
+                let used_input = PathBuf::from(format!(
                     "{}/{}/{}",
                     parent_path.display(),
                     USED_ITEM_SUBDIR_NAME,
                     input.file_name().unwrap().display()
-                ),
-            )
-            .unwrap();
+                ));
+                fs::rename(input.clone(), &used_input).unwrap();
+                move_commit_status(&input, &used_input).unwrap();
+            }
         }
     }
 
-    task_status.write().unwrap().busy = None;
+    *task_status.write().unwrap() = TaskStatus::default();
     Response::Craft(r.map(|_| output))
 }
 fn commit(
@@ -139,3 +161,16 @@ fn commit(
     task_status.write().unwrap().busy = None;
     Response::Commit(r.map(|_| input))
 }
+fn destroy(
+    task_status: &RwLock<TaskStatus>,
+    params: &Params,
+    cfg: Config,
+    input: PathBuf,
+) -> Response {
+    set_busy_task(task_status, "Destroying");
+
+    let rt = Runtime::new().unwrap();
+    let r = rt.block_on(async { destroy_item(params, &cfg, &input).await });
+    task_status.write().unwrap().busy = None;
+    Response::Destroy(r.map(|_| input))
+}
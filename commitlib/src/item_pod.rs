@@ -0,0 +1,117 @@
+//! MainPod of an item, with its public statements located by predicate instead
+//! of by position.
+
+use anyhow::{Result, anyhow, bail};
+use pod2::{
+    frontend::MainPod,
+    middleware::{CustomPredicateRef, Hash, Statement, Value},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::predicates::CommitPredicates;
+
+/// Pod proving the public statements of an item:
+/// - ItemKey, to consume it via Nullifiers
+/// - ItemDef, to expose its properties and commit it via CommitCreation
+/// - Nullifiers of its inputs, to commit it via CommitCreation, if it was crafted locally
+/// - The app layer predicate of its recipe, to use it as an input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "MainPod", into = "MainPod")]
+pub struct ItemPod {
+    pod: MainPod,
+    item: Hash,
+    item_key: usize,
+    item_def: usize,
+    nullifiers: Option<usize>,
+    craft: Option<usize>,
+}
+
+impl ItemPod {
+    /// Locates the public statements of `pod`, checking that they describe a
+    /// single item.
+    pub fn new(pod: MainPod, commit_preds: &CommitPredicates) -> Result<Self> {
+        let find =
+            |name: &str, is_match: &dyn Fn(&CustomPredicateRef) -> bool| -> Result<Option<usize>> {
+                let mut found = pod
+                    .public_statements
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, st)| matches!(st, Statement::Custom(pred, _) if is_match(pred)))
+                    .map(|(i, _)| i);
+                match (found.next(), found.next()) {
+                    (_, Some(_)) => bail!("Item pod has more than one {name} statement"),
+                    (i, None) => Ok(i),
+                }
+            };
+        let item_key = find("ItemKey", &|pred| *pred == commit_preds.item_key)?
+            .ok_or_else(|| anyhow!("Item pod has no ItemKey statement"))?;
+        let item_def = find("ItemDef", &|pred| *pred == commit_preds.item_def)?
+            .ok_or_else(|| anyhow!("Item pod has no ItemDef statement"))?;
+        let nullifiers = find("Nullifiers", &|pred| *pred == commit_preds.nullifiers)?;
+        // The only statement from outside of the commit predicates
+        let craft = find("app layer", &|pred| {
+            !commit_preds.defs.batch_ids.contains(&pred.batch.id())
+        })?;
+
+        let item = Hash::from(pod.public_statements[item_def].args()[0].literal()?.raw());
+        let key_item = Hash::from(pod.public_statements[item_key].args()[0].literal()?.raw());
+        if key_item != item {
+            bail!("ItemKey of item {key_item:#} doesn't match ItemDef of item {item:#}");
+        }
+        if let Some(craft) = craft
+            && *pod.public_statements[craft].args()[0].literal()? != Value::from(item)
+        {
+            bail!("App layer statement is not about item {item:#}");
+        }
+
+        Ok(Self {
+            pod,
+            item,
+            item_key,
+            item_def,
+            nullifiers,
+            craft,
+        })
+    }
+
+    pub fn pod(&self) -> &MainPod {
+        &self.pod
+    }
+
+    /// Hash of the item, which identifies it on-chain
+    pub fn item(&self) -> Hash {
+        self.item
+    }
+
+    pub fn st_item_key(&self) -> &Statement {
+        &self.pod.public_statements[self.item_key]
+    }
+
+    pub fn st_item_def(&self) -> &Statement {
+        &self.pod.public_statements[self.item_def]
+    }
+
+    pub fn st_nullifiers(&self) -> Option<&Statement> {
+        self.nullifiers.map(|i| &self.pod.public_statements[i])
+    }
+
+    /// Statement of the app layer predicate proving the recipe the item follows
+    pub fn st_craft(&self) -> Option<&Statement> {
+        self.craft.map(|i| &self.pod.public_statements[i])
+    }
+}
+
+impl TryFrom<MainPod> for ItemPod {
+    type Error = anyhow::Error;
+
+    fn try_from(pod: MainPod) -> Result<Self> {
+        let commit_preds = CommitPredicates::compile(&pod.params);
+        Self::new(pod, &commit_preds)
+    }
+}
+
+impl From<ItemPod> for MainPod {
+    fn from(item_pod: ItemPod) -> Self {
+        item_pod.pod
+    }
+}
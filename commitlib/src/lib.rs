@@ -1,3 +1,5 @@
+pub mod item_pod;
+pub mod planner;
 pub mod predicates;
 pub mod util;
 
@@ -14,6 +16,24 @@ use serde::{Deserialize, Serialize};
 use crate::util::set_from_hashes;
 
 pub const CONSUMED_ITEM_EXTERNAL_NULLIFIER: &str = "consumed item external nullifier";
+pub const TRANSFER_KEY_COMMITMENT: &str = "transfer key commitment";
+/// Ingredients field holding the key commitment of the recipient of a transferred item
+pub const KEY_COMMITMENT_FIELD: &str = "key_commitment";
+
+// Nullifier published when consuming an item whose ItemKey is `key`, as computed in
+// `st_nullifiers`.
+pub fn nullifier_of_key(key: RawValue) -> Hash {
+    hash_values(&[
+        Value::from(key),
+        Value::from(CONSUMED_ITEM_EXTERNAL_NULLIFIER),
+    ])
+}
+
+// Commitment to the key chosen by the recipient of a transfer, as checked by
+// TransferredItemKey.
+pub fn key_commitment(key: RawValue) -> Hash {
+    hash_values(&[Value::from(key), Value::from(TRANSFER_KEY_COMMITMENT)])
+}
 
 // Rust-level definition of the ingredients of an item, used to derive the
 // ingredients hash (dict root) before doing sequential work on it.
@@ -62,11 +82,27 @@ impl ItemDef {
         ]))
     }
 
-    pub fn nullifier(&self, params: &Params) -> pod2::middleware::Result<Hash> {
-        Ok(hash_values(&[
-            Value::from(self.item_hash(params)?),
-            Value::from(CONSUMED_ITEM_EXTERNAL_NULLIFIER),
-        ]))
+    // Nullifier published when the item is consumed, as computed in `st_nullifiers`.
+    // Only valid for items that were never transferred, which are consumed with the
+    // key of their ingredients.
+    pub fn nullifier(&self) -> Hash {
+        nullifier_of_key(self.ingredients.key)
+    }
+
+    // Definition of the item created by transferring this one to the owner of
+    // `key_commitment`, as proven by TransferredItemDef.
+    pub fn transferred(&self, key_commitment: Hash) -> Self {
+        let mut transferred = self.clone();
+        transferred.ingredients.app_layer.insert(
+            KEY_COMMITMENT_FIELD.to_string(),
+            Value::from(key_commitment),
+        );
+        transferred
+    }
+
+    // Key commitment of the owner of a transferred item
+    pub fn key_commitment(&self) -> Option<&Value> {
+        self.ingredients.app_layer.get(KEY_COMMITMENT_FIELD)
     }
 
     pub fn new(ingredients: IngredientsDef, work: RawValue) -> Self {
@@ -108,11 +144,86 @@ impl<'a> ItemBuilder<'a> {
         ))?)
     }
 
+    // Builds ItemKey for an item that was never transferred, from its ItemDef.
     pub fn st_item_key(&mut self, st_item_def: Statement) -> anyhow::Result<Statement> {
+        let ingredients_dict = st_item_def.args()[1].literal().unwrap().clone();
+
+        // Build CraftedItemKey(item, key)
+        let st_crafted_item_key = st_custom!(self.ctx,
+        CraftedItemKey() = (
+            st_item_def,
+            DictNotContains(ingredients_dict, KEY_COMMITMENT_FIELD)
+        ))?;
+
         // Build ItemKey(item, key)
         Ok(st_custom!(self.ctx,
         ItemKey() = (
-            st_item_def
+            st_crafted_item_key,
+            Statement::None
+        ))?)
+    }
+
+    // Builds ItemKey for a transferred item from its definition and the key whose
+    // commitment it holds.
+    pub fn st_transferred_item_key(
+        &mut self,
+        item_def: ItemDef,
+        key: RawValue,
+    ) -> anyhow::Result<Statement> {
+        let ingredients_dict = item_def.ingredients.dict(self.params)?;
+        let item_hash = item_def.item_hash(self.params)?;
+        let key_commitment = key_commitment(key);
+
+        // Build TransferredItemKey(item, key)
+        let st_transferred_item_key = st_custom!(self.ctx,
+        TransferredItemKey() = (
+            DictContains(ingredients_dict, KEY_COMMITMENT_FIELD, key_commitment),
+            HashOf(key_commitment, key, TRANSFER_KEY_COMMITMENT),
+            HashOf(item_hash, ingredients_dict, item_def.work)
+        ))?;
+
+        // Build ItemKey(item, key)
+        Ok(st_custom!(self.ctx,
+        ItemKey() = (
+            Statement::None,
+            st_transferred_item_key
+        ))?)
+    }
+
+    // Builds TransferredItemDef(item, original, key_commitment) for the transfer of
+    // `original` to the owner of `key_commitment`.
+    pub fn st_transferred_item_def(
+        &mut self,
+        original: ItemDef,
+        key_commitment: Hash,
+    ) -> anyhow::Result<Statement> {
+        let item_def = original.transferred(key_commitment);
+        let original_dict = original.ingredients.dict(self.params)?;
+        let ingredients_dict = item_def.ingredients.dict(self.params)?;
+        let original_hash = original.item_hash(self.params)?;
+        let item_hash = item_def.item_hash(self.params)?;
+
+        // Build TransferredIngredients(ingredients, original_ingredients, key_commitment)
+        let st_transferred_ingredients = if original.key_commitment().is_none() {
+            st_custom!(self.ctx,
+            TransferredIngredients() = (
+                DictInsert(ingredients_dict, original_dict, KEY_COMMITMENT_FIELD, key_commitment),
+                Statement::None
+            ))?
+        } else {
+            st_custom!(self.ctx,
+            TransferredIngredients() = (
+                Statement::None,
+                DictUpdate(ingredients_dict, original_dict, KEY_COMMITMENT_FIELD, key_commitment)
+            ))?
+        };
+
+        // Build TransferredItemDef(item, original, key_commitment)
+        Ok(st_custom!(self.ctx,
+        TransferredItemDef() = (
+            HashOf(original_hash, original_dict, original.work),
+            st_transferred_ingredients,
+            HashOf(item_hash, ingredients_dict, item_def.work)
         ))?)
     }
 
@@ -243,6 +354,53 @@ impl<'a> ItemBuilder<'a> {
             ))?;
         Ok(st_commit_creation)
     }
+
+    // Builds the public POD to commit a destruction operation on-chain, with the
+    // only public predicate being CommitDestruction.  The inputs are the items
+    // whose nullifiers are proven by st_nullifiers.
+    pub fn st_commit_destruction(
+        &mut self,
+        inputs_set: Set,
+        st_nullifiers: Statement,
+        created_items: Set,
+    ) -> anyhow::Result<Statement> {
+        let st_inputs_subset = self.st_super_sub_set(inputs_set, created_items)?;
+
+        // Build CommitDestruction(nullifiers, created_items)
+        let st_commit_destruction = st_custom!(self.ctx,
+            CommitDestruction() = (
+                st_inputs_subset,
+                st_nullifiers
+            ))?;
+        Ok(st_commit_destruction)
+    }
+
+    // Builds the public POD to commit a transfer operation on-chain, with the only
+    // public predicate being CommitTransfer.  The nullifier of `original` is
+    // proven by st_nullifiers.
+    pub fn st_commit_transfer(
+        &mut self,
+        original: ItemDef,
+        key_commitment: Hash,
+        st_nullifiers: Statement,
+        created_items: Set,
+    ) -> anyhow::Result<Statement> {
+        let original_hash = original.item_hash(self.params)?;
+        let empty_set = set!(self.params.max_depth_mt_containers)?;
+        let inputs_set = set_from_hashes(self.params, &HashSet::from([original_hash]))?;
+        let st_transferred_item_def = self.st_transferred_item_def(original, key_commitment)?;
+        let st_inputs_subset = self.st_super_sub_set(inputs_set.clone(), created_items)?;
+
+        // Build CommitTransfer(item, nullifiers, created_items)
+        let st_commit_transfer = st_custom!(self.ctx,
+            CommitTransfer() = (
+                st_transferred_item_def,
+                SetInsert(inputs_set, empty_set, original_hash),
+                st_inputs_subset,
+                st_nullifiers
+            ))?;
+        Ok(st_commit_transfer)
+    }
 }
 
 #[cfg(test)]
@@ -258,7 +416,7 @@ mod tests {
     };
 
     use super::*;
-    use crate::predicates::CommitPredicates;
+    use crate::{item_pod::ItemPod, planner::MainPodPlanner, predicates::CommitPredicates};
 
     #[allow(clippy::too_many_arguments)]
     fn build_item(
@@ -271,8 +429,9 @@ mod tests {
         key: i64,
         input_item_key_pods: Vec<MainPod>,
     ) -> MainPod {
-        let mut builder = MainPodBuilder::new(params, vd_set);
-        let mut item_builder = ItemBuilder::new(BuildContext::new(&mut builder, batches), params);
+        let mut planner = MainPodPlanner::new(params, vd_set);
+        let mut item_builder =
+            ItemBuilder::new(BuildContext::new(planner.builder(), batches), params);
 
         let mut input_item_hashes = HashSet::new();
         let mut sts_item_key = Vec::new();
@@ -294,28 +453,13 @@ mod tests {
             ingredients: ingredients_def,
             work: Value::from(42).raw(),
         };
-        let (st_nullifiers, _nullifiers) = if sts_item_key.is_empty() {
-            item_builder.st_nullifiers(sts_item_key).unwrap()
-        } else {
-            // The default params don't have enough custom statement verifications to fit
-            // everything in a single pod, so we split it in two.
-            let (st_nullifiers, nullifiers) = item_builder.st_nullifiers(sts_item_key).unwrap();
-            item_builder.ctx.builder.reveal(&st_nullifiers);
-
-            println!("Proving nullifiers_pod for {blueprint}...");
-            let nullifiers_pod = builder.prove(prover).unwrap();
-            nullifiers_pod.pod.verify().unwrap();
-            builder = MainPodBuilder::new(params, vd_set);
-            item_builder = ItemBuilder::new(BuildContext::new(&mut builder, batches), params);
-            item_builder.ctx.builder.add_pod(nullifiers_pod);
-            (st_nullifiers, nullifiers)
-        };
+        let (st_nullifiers, _nullifiers) = item_builder.st_nullifiers(sts_item_key).unwrap();
 
         let item_hash = item_def.item_hash(params).unwrap();
         created_items.insert(&Value::from(item_hash)).unwrap();
         let st_item_def = item_builder.st_item_def(item_def.clone()).unwrap();
 
-        let _st_commit_creation = item_builder
+        let st_commit_creation = item_builder
             .st_commit_creation(
                 item_def.clone(),
                 st_nullifiers,
@@ -323,9 +467,10 @@ mod tests {
                 st_item_def,
             )
             .unwrap();
+        item_builder.ctx.builder.reveal(&st_commit_creation);
 
         println!("Proving commit_pod for {blueprint}...");
-        let commit_pod = builder.prove(prover).unwrap();
+        let commit_pod = planner.prove(prover).unwrap();
         commit_pod.pod.verify().unwrap();
 
         let mut builder = MainPodBuilder::new(params, vd_set);
@@ -397,4 +542,267 @@ mod tests {
             vec![item_key_pod_na, item_key_pod_cl],
         );
     }
+
+    #[test]
+    fn test_prove_st_commit_creation_many_inputs() {
+        let prover = &MockProver {};
+        let vd_set = &VDSet::new(6, &[]).unwrap();
+        let params = Params::default();
+        let commit_preds = CommitPredicates::compile(&params);
+        let batches = &commit_preds.defs.batches;
+        let mut created_items = set_from_hashes(&params, &HashSet::new()).unwrap();
+
+        // More inputs than fit in a single pod, so that the planner splits the
+        // proof in a chain of pods.
+        let input_item_key_pods = (1..=5)
+            .map(|key| {
+                build_item(
+                    &params,
+                    vd_set,
+                    prover,
+                    batches,
+                    &mut created_items,
+                    "h",
+                    key,
+                    vec![],
+                )
+            })
+            .collect();
+        let _item_key_pod = build_item(
+            &params,
+            vd_set,
+            prover,
+            batches,
+            &mut created_items,
+            "h5",
+            6,
+            input_item_key_pods,
+        );
+    }
+
+    #[test]
+    fn test_prove_st_commit_destruction() {
+        let mock_prover = MockProver {};
+        let prover: &dyn MainPodProver = &mock_prover;
+        let vd_set = &VDSet::new(6, &[]).unwrap();
+
+        let params = Params::default();
+
+        let commit_preds = CommitPredicates::compile(&params);
+        let batches = &commit_preds.defs.batches;
+
+        let mut created_items = set_from_hashes(&params, &HashSet::new()).unwrap();
+
+        let item_key_pod = build_item(
+            &params,
+            vd_set,
+            prover,
+            batches,
+            &mut created_items,
+            "na",
+            1,
+            vec![],
+        );
+        let st_item_key = item_key_pod.pod.pub_statements()[0].clone();
+        let item_hash = Hash::from(st_item_key.args()[0].literal().unwrap().raw());
+
+        let mut planner = MainPodPlanner::new(&params, vd_set);
+        let mut item_builder =
+            ItemBuilder::new(BuildContext::new(planner.builder(), batches), &params);
+        item_builder.ctx.builder.add_pod(item_key_pod);
+        let (st_nullifiers, nullifiers) = item_builder.st_nullifiers(vec![st_item_key]).unwrap();
+        let inputs_set = set_from_hashes(&params, &HashSet::from([item_hash])).unwrap();
+        let st_commit_destruction = item_builder
+            .st_commit_destruction(inputs_set, st_nullifiers, created_items)
+            .unwrap();
+        item_builder.ctx.builder.reveal(&st_commit_destruction);
+
+        println!("Proving destruction_pod...");
+        let destruction_pod = planner.prove(prover).unwrap();
+        destruction_pod.pod.verify().unwrap();
+
+        assert_eq!(
+            *st_commit_destruction.args()[0].literal().unwrap(),
+            Value::from(nullifiers)
+        );
+    }
+
+    #[test]
+    fn test_item_pod() {
+        let prover = &MockProver {};
+        let vd_set = &VDSet::new(6, &[]).unwrap();
+        let params = Params::default();
+        let commit_preds = CommitPredicates::compile(&params);
+        let batches = &commit_preds.defs.batches;
+
+        let item_def = ItemDef::new(
+            IngredientsDef {
+                inputs: HashSet::new(),
+                key: Value::from(1).raw(),
+                app_layer: HashMap::from([("blueprint".to_string(), Value::from("na"))]),
+            },
+            Value::from(42).raw(),
+        );
+        let prove_item_pod = |reveal_item_def: bool| {
+            let mut builder = MainPodBuilder::new(&params, vd_set);
+            let mut item_builder =
+                ItemBuilder::new(BuildContext::new(&mut builder, batches), &params);
+            let st_item_def = item_builder.st_item_def(item_def.clone()).unwrap();
+            let st_item_key = item_builder.st_item_key(st_item_def.clone()).unwrap();
+            // ItemPod doesn't depend on the order of the statements
+            if reveal_item_def {
+                builder.reveal(&st_item_def);
+            }
+            builder.reveal(&st_item_key);
+            builder.prove(prover).unwrap()
+        };
+
+        let item_pod = ItemPod::new(prove_item_pod(true), &commit_preds).unwrap();
+        assert_eq!(item_pod.item(), item_def.item_hash(&params).unwrap());
+        assert_eq!(
+            *item_pod.st_item_key().args()[1].literal().unwrap(),
+            Value::from(1)
+        );
+        assert!(item_pod.st_nullifiers().is_none());
+        assert!(item_pod.st_craft().is_none());
+
+        assert!(ItemPod::new(prove_item_pod(false), &commit_preds).is_err());
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn transfer_item(
+        params: &Params,
+        vd_set: &VDSet,
+        prover: &dyn MainPodProver,
+        batches: &[Arc<CustomPredicateBatch>],
+        created_items: &mut Set,
+        original: &ItemDef,
+        item_key_pod: MainPod,
+        key_commitment: Hash,
+    ) -> Statement {
+        let st_item_key = item_key_pod.pod.pub_statements()[0].clone();
+
+        let transferred = original.transferred(key_commitment);
+        created_items
+            .insert(&Value::from(transferred.item_hash(params).unwrap()))
+            .unwrap();
+
+        let mut planner = MainPodPlanner::new(params, vd_set);
+        let mut item_builder =
+            ItemBuilder::new(BuildContext::new(planner.builder(), batches), params);
+        item_builder.ctx.builder.add_pod(item_key_pod);
+        let (st_nullifiers, _nullifiers) = item_builder.st_nullifiers(vec![st_item_key]).unwrap();
+        let st_commit_transfer = item_builder
+            .st_commit_transfer(
+                original.clone(),
+                key_commitment,
+                st_nullifiers,
+                created_items.clone(),
+            )
+            .unwrap();
+        item_builder.ctx.builder.reveal(&st_commit_transfer);
+
+        println!("Proving transfer_pod...");
+        let transfer_pod = planner.prove(prover).unwrap();
+        transfer_pod.pod.verify().unwrap();
+
+        st_commit_transfer
+    }
+
+    fn transferred_item_key_pod(
+        params: &Params,
+        vd_set: &VDSet,
+        prover: &dyn MainPodProver,
+        batches: &[Arc<CustomPredicateBatch>],
+        item_def: ItemDef,
+        key: RawValue,
+    ) -> MainPod {
+        let mut builder = MainPodBuilder::new(params, vd_set);
+        let mut item_builder = ItemBuilder::new(BuildContext::new(&mut builder, batches), params);
+        let st_item_key = item_builder.st_transferred_item_key(item_def, key).unwrap();
+        item_builder.ctx.builder.reveal(&st_item_key);
+
+        println!("Proving transferred item_key_pod...");
+        let item_key_pod = builder.prove(prover).unwrap();
+        item_key_pod.pod.verify().unwrap();
+        item_key_pod
+    }
+
+    #[test]
+    fn test_prove_st_commit_transfer() {
+        let mock_prover = MockProver {};
+        let prover: &dyn MainPodProver = &mock_prover;
+        let vd_set = &VDSet::new(6, &[]).unwrap();
+
+        let params = Params::default();
+
+        let commit_preds = CommitPredicates::compile(&params);
+        let batches = &commit_preds.defs.batches;
+
+        let mut created_items = set_from_hashes(&params, &HashSet::new()).unwrap();
+
+        let item_key_pod = build_item(
+            &params,
+            vd_set,
+            prover,
+            batches,
+            &mut created_items,
+            "na",
+            1,
+            vec![],
+        );
+        // Same definition as built by `build_item`
+        let original = ItemDef::new(
+            IngredientsDef {
+                inputs: HashSet::new(),
+                key: Value::from(1).raw(),
+                app_layer: HashMap::from([("blueprint".to_string(), Value::from("na"))]),
+            },
+            Value::from(42).raw(),
+        );
+
+        // First transfer inserts the key commitment
+        let key = Value::from(2).raw();
+        let st_commit_transfer = transfer_item(
+            &params,
+            vd_set,
+            prover,
+            batches,
+            &mut created_items,
+            &original,
+            item_key_pod,
+            key_commitment(key),
+        );
+        let transferred = original.transferred(key_commitment(key));
+        assert_eq!(
+            *st_commit_transfer.args()[0].literal().unwrap(),
+            Value::from(transferred.item_hash(&params).unwrap())
+        );
+        let nullifiers = set_from_hashes(&params, &HashSet::from([original.nullifier()])).unwrap();
+        assert_eq!(
+            *st_commit_transfer.args()[1].literal().unwrap(),
+            Value::from(nullifiers)
+        );
+
+        // The recipient consumes the item with its own key, so it can transfer it
+        // again, which updates the key commitment
+        let item_key_pod =
+            transferred_item_key_pod(&params, vd_set, prover, batches, transferred.clone(), key);
+        let next_key = Value::from(3).raw();
+        let st_commit_transfer = transfer_item(
+            &params,
+            vd_set,
+            prover,
+            batches,
+            &mut created_items,
+            &transferred,
+            item_key_pod,
+            key_commitment(next_key),
+        );
+        let nullifiers = set_from_hashes(&params, &HashSet::from([nullifier_of_key(key)])).unwrap();
+        assert_eq!(
+            *st_commit_transfer.args()[1].literal().unwrap(),
+            Value::from(nullifiers)
+        );
+    }
 }
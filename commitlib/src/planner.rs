@@ -0,0 +1,220 @@
+//! Planner that splits the proving of a set of statements into a chain of
+//! MainPods within the limits of `Params`.
+//!
+//! The operations are first recorded on a `MainPodBuilder` without limits, as
+//! if everything fit in a single pod.  On `prove`, they are replayed in order
+//! into as many pods as needed: each pod takes the previous one as input and
+//! reveals the statements that later operations depend on, and the last one
+//! reveals the public statements of the recording builder, in order.
+
+use anyhow::{Result, anyhow, bail};
+use log::info;
+use pod2::{
+    frontend::{MainPod, MainPodBuilder, Operation, OperationArg},
+    middleware::{Hash, MainPodProver, OperationType, Params, Statement, VDSet},
+};
+
+/// Factor by which the limits of the recording builder exceed the ones of a pod
+const RECORDING_SCALE: usize = 1 << 10;
+
+pub struct MainPodPlanner {
+    params: Params,
+    vd_set: VDSet,
+    builder: MainPodBuilder,
+}
+
+// Operations replayed into a single pod of the chain
+#[derive(Clone, Default)]
+struct Chunk {
+    // Whether the pod takes the previous pod of the chain as input
+    has_previous: bool,
+    // Indexes of the recorded input pods
+    input_pods: Vec<usize>,
+    // Indexes of the recorded operations
+    ops: Vec<usize>,
+    // Statements created by the operations
+    created: Vec<Statement>,
+    // Statements the operations can depend on: the ones carried by the
+    // previous pod, taken from the input pods or created by the operations
+    available: Vec<Statement>,
+    custom_batches: Vec<Hash>,
+    custom_verifications: usize,
+}
+
+impl Chunk {
+    fn fits(&self, params: &Params, public: &[Statement]) -> bool {
+        let num_input_pods = self.input_pods.len() + usize::from(self.has_previous);
+        // Public statements not created by the pod are copied from its inputs
+        let num_copies = public
+            .iter()
+            .filter(|st| !self.created.contains(st))
+            .count();
+        num_input_pods <= params.max_input_pods
+            && self.ops.len() + num_copies <= params.max_priv_statements()
+            && public.len() <= params.max_public_statements
+            && self.custom_verifications <= params.max_custom_predicate_verifications
+            && self.custom_batches.len() <= params.max_custom_predicate_batches
+    }
+}
+
+impl MainPodPlanner {
+    pub fn new(params: &Params, vd_set: &VDSet) -> Self {
+        // The recording builder is never proved, so it can go past the limits
+        // on the size of a pod.
+        let unbounded = Params {
+            max_input_pods: params.max_input_pods * RECORDING_SCALE,
+            max_statements: params.max_statements * RECORDING_SCALE,
+            max_public_statements: params.max_public_statements * RECORDING_SCALE,
+            max_custom_predicate_batches: params.max_custom_predicate_batches * RECORDING_SCALE,
+            max_custom_predicate_verifications: params.max_custom_predicate_verifications
+                * RECORDING_SCALE,
+            ..params.clone()
+        };
+        Self {
+            params: params.clone(),
+            vd_set: vd_set.clone(),
+            builder: MainPodBuilder::new(&unbounded, vd_set),
+        }
+    }
+
+    /// Builder on which to record the input pods, operations and public
+    /// statements.
+    pub fn builder(&mut self) -> &mut MainPodBuilder {
+        &mut self.builder
+    }
+
+    /// Proves the recorded operations in as many pods as needed, returning the
+    /// last one, which holds the public statements of the recording builder.
+    pub fn prove(self, prover: &dyn MainPodProver) -> Result<MainPod> {
+        let Self {
+            params,
+            vd_set,
+            builder,
+        } = self;
+        let ops: Vec<(Statement, Operation)> = builder
+            .statements
+            .into_iter()
+            .zip(builder.operations)
+            .collect();
+        let input_pods = builder.input_pods;
+        let public_statements = builder.public_statements;
+
+        // Whether a statement is needed after the operation at `step`
+        let used_after = |st: &Statement, step: usize| {
+            public_statements.contains(st)
+                || ops[step + 1..]
+                    .iter()
+                    .any(|(_, op)| references(op).any(|arg| arg == st))
+        };
+        // Statements a pod reveals for the next pods of the chain
+        let carried = |chunk: &Chunk| -> Vec<Statement> {
+            match chunk.ops.last() {
+                Some(&last) => chunk
+                    .available
+                    .iter()
+                    .filter(|st| used_after(st, last))
+                    .cloned()
+                    .collect(),
+                None => chunk.available.clone(),
+            }
+        };
+        // Adds the operation at `step` to a chunk, or the final step revealing
+        // the public statements after all the operations.  Returns the new
+        // chunk and the statements it would reveal.
+        let extend = |chunk: &Chunk, step: usize| -> Result<(Chunk, Vec<Statement>)> {
+            let mut chunk = chunk.clone();
+            let needs: Vec<&Statement> = match ops.get(step) {
+                Some((_, op)) => references(op).collect(),
+                None => public_statements.iter().collect(),
+            };
+            for st in needs {
+                if chunk.available.contains(st) {
+                    continue;
+                }
+                // The statements of the previous pods needed later are carried
+                // over, so the missing ones come from an input pod.
+                let pod = input_pods
+                    .iter()
+                    .position(|pod| pod.public_statements.contains(st))
+                    .ok_or_else(|| anyhow!("no input pod has the statement {st:?}"))?;
+                chunk.input_pods.push(pod);
+                chunk
+                    .available
+                    .extend(input_pods[pod].public_statements.iter().cloned());
+            }
+            let Some((st, op)) = ops.get(step) else {
+                return Ok((chunk, public_statements.clone()));
+            };
+            chunk.ops.push(step);
+            chunk.created.push(st.clone());
+            chunk.available.push(st.clone());
+            if let OperationType::Custom(pred) = &op.0 {
+                chunk.custom_verifications += 1;
+                if !chunk.custom_batches.contains(&pred.batch.id()) {
+                    chunk.custom_batches.push(pred.batch.id());
+                }
+            }
+            let public = carried(&chunk);
+            Ok((chunk, public))
+        };
+
+        let mut chunks = vec![Chunk::default()];
+        for step in 0..=ops.len() {
+            let last = chunks.last_mut().expect("at least one chunk");
+            let (chunk, public) = extend(last, step)?;
+            if chunk.fits(&params, &public) {
+                *last = chunk;
+                continue;
+            }
+            if last.ops.is_empty() {
+                bail!("step {step} doesn't fit in a pod");
+            }
+            let next = Chunk {
+                has_previous: true,
+                available: carried(last),
+                ..Default::default()
+            };
+            let (chunk, public) = extend(&next, step)?;
+            if !chunk.fits(&params, &public) {
+                bail!("step {step} doesn't fit in a pod");
+            }
+            chunks.push(chunk);
+        }
+
+        let num_pods = chunks.len();
+        let mut previous_pod: Option<MainPod> = None;
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut builder = MainPodBuilder::new(&params, &vd_set);
+            if let Some(pod) = previous_pod.take() {
+                builder.add_pod(pod);
+            }
+            for pod in &chunk.input_pods {
+                builder.add_pod(input_pods[*pod].clone());
+            }
+            for op in &chunk.ops {
+                builder.op(false, vec![], ops[*op].1.clone())?;
+            }
+            let public = if i + 1 == num_pods {
+                public_statements.clone()
+            } else {
+                carried(chunk)
+            };
+            for st in &public {
+                builder.reveal(st);
+            }
+            info!("Proving pod {}/{num_pods}...", i + 1);
+            let pod = builder.prove(prover)?;
+            pod.pod.verify()?;
+            previous_pod = Some(pod);
+        }
+        Ok(previous_pod.expect("at least one chunk"))
+    }
+}
+
+// Statements an operation depends on
+fn references(op: &Operation) -> impl Iterator<Item = &Statement> {
+    op.1.iter().filter_map(|arg| match arg {
+        OperationArg::Statement(st) => Some(st),
+        _ => None,
+    })
+}
@@ -1,20 +1,27 @@
 use pod2::middleware::{CustomPredicateRef, Params};
 use pod2utils::PredicateDefs;
 
-use crate::CONSUMED_ITEM_EXTERNAL_NULLIFIER;
+use crate::{CONSUMED_ITEM_EXTERNAL_NULLIFIER, TRANSFER_KEY_COMMITMENT};
 
 pub struct CommitPredicates {
     pub defs: PredicateDefs,
 
-    pub subset_of: CustomPredicateRef,
-    pub subset_of_recursive: CustomPredicateRef,
     pub item_def: CustomPredicateRef,
+    pub crafted_item_key: CustomPredicateRef,
+    pub transferred_item_key: CustomPredicateRef,
     pub item_key: CustomPredicateRef,
 
+    pub subset_of: CustomPredicateRef,
+    pub subset_of_recursive: CustomPredicateRef,
+    pub transferred_ingredients: CustomPredicateRef,
+    pub transferred_item_def: CustomPredicateRef,
+
     pub nullifiers: CustomPredicateRef,
     pub nullifiers_empty: CustomPredicateRef,
     pub nullifiers_recursive: CustomPredicateRef,
     pub commit_creation: CustomPredicateRef,
+    pub commit_destruction: CustomPredicateRef,
+    pub commit_transfer: CustomPredicateRef,
 }
 
 impl CommitPredicates {
@@ -24,7 +31,41 @@ impl CommitPredicates {
         // 4 predicates per batch
         // 8 arguments per predicate, at most 5 of which are public
         // 5 statements per predicate
-        let batch_defs = [
+        let batch_defs: [&str; 4] = [
+            &format!(
+                r#"
+            // Prove proper derivation of item ID from defined inputs
+            // The ingredients dict is explicitly allowed to contain more fields
+            // for use in item predicates.
+            ItemDef(item, ingredients, inputs, key, work) = AND(
+                DictContains(ingredients, "inputs", inputs)
+                DictContains(ingredients, "key", key)
+                HashOf(item, ingredients, work)
+            )
+
+            // Key of an item that was never transferred: the one chosen by its
+            // crafter.
+            CraftedItemKey(item, key, private: ingredients, inputs, work) = AND(
+                ItemDef(item, ingredients, inputs, key, work)
+                DictNotContains(ingredients, "key_commitment")
+            )
+
+            // Key of a transferred item: the preimage of the key commitment chosen
+            // by its recipient.  The key of the original owner is still in the
+            // ingredients, but it can't be used to consume the item anymore.
+            TransferredItemKey(item, key, private: ingredients, key_commitment, work) = AND(
+                DictContains(ingredients, "key_commitment", key_commitment)
+                HashOf(key_commitment, key, "{TRANSFER_KEY_COMMITMENT}")
+                HashOf(item, ingredients, work)
+            )
+
+            // Helper to expose just the item and the key that consumes it.
+            ItemKey(item, key) = OR(
+                CraftedItemKey(item, key)
+                TransferredItemKey(item, key)
+            )
+            "#
+            ),
             r#"
             // Generic recursive construction confirming subset.  Relies on the Merkle
             // tree already requiring unique keys (so no inserts on super)
@@ -39,19 +80,21 @@ impl CommitPredicates {
                 SubsetOf(smaller, super)
             )
 
-            // Prove proper derivation of item ID from defined inputs
-            // The ingredients dict is explicitly allowed to contain more fields
-            // for use in item predicates.
-            ItemDef(item, ingredients, inputs, key, work) = AND(
-                DictContains(ingredients, "inputs", inputs)
-                DictContains(ingredients, "key", key)
-                HashOf(item, ingredients, work)
+            // The ingredients of a transferred item are the ones of the original
+            // item with the key commitment of the recipient.  The original item may
+            // have been transferred already.
+            TransferredIngredients(ingredients, original_ingredients, key_commitment) = OR(
+                DictInsert(ingredients, original_ingredients, "key_commitment", key_commitment)
+                DictUpdate(ingredients, original_ingredients, "key_commitment", key_commitment)
             )
 
-            // Helper to expose just the item and key from ItemId calculation.
-            // This is just the CreatedItem pattern with some of inupts private.
-            ItemKey(item, key, private: ingredients, inputs, work) = AND(
-                ItemDef(item, ingredients, inputs, key, work)
+            // Prove derivation of a transferred item ID from the original item,
+            // keeping its inputs, attributes and work.
+            TransferredItemDef(item, original, key_commitment,
+                    private: ingredients, original_ingredients, work) = AND(
+                HashOf(original, original_ingredients, work)
+                TransferredIngredients(ingredients, original_ingredients, key_commitment)
+                HashOf(item, ingredients, work)
             )
             "#,
             &format!(
@@ -98,19 +141,59 @@ impl CommitPredicates {
             )
             "#
             ),
+            r#"
+            // ZK destruction of items for committing on-chain.  Publishes the
+            // nullifiers of the inputs without creating a new item, so that
+            // they can't be consumed anymore.  Accepted iff:
+            // - all nullifiers are not already in nullifier set
+            // - createdItems is one of the historical item set roots
+            CommitDestruction(nullifiers, created_items, private: inputs) = AND(
+                // Prove all inputs are in the created set
+                SubsetOf(inputs, created_items)
+
+                // Expose nullifiers for all inputs
+                Nullifiers(nullifiers, inputs)
+            )
+
+            // ZK transfer of an item for committing on-chain.  Consumes the
+            // original item and creates an item with the same properties bound to
+            // the key commitment of the recipient.  Accepted iff:
+            // - item is not already in item set
+            // - the nullifier is not already in nullifier set
+            // - createdItems is one of the historical item set roots
+            CommitTransfer(item, nullifiers, created_items,
+                    private: original, key_commitment, inputs) = AND(
+                TransferredItemDef(item, original, key_commitment)
+                SetInsert(inputs, {}, original)
+
+                // Prove the original item is in the created set
+                SubsetOf(inputs, created_items)
+
+                // Expose the nullifier of the original item
+                Nullifiers(nullifiers, inputs)
+            )
+            "#,
         ];
 
         let defs = PredicateDefs::new(params, &batch_defs, &[]);
 
         CommitPredicates {
-            subset_of: defs.predicate_ref_by_name("SubsetOf").unwrap(),
-            subset_of_recursive: defs.predicate_ref_by_name("SubsetOfRecursive").unwrap(),
             item_def: defs.predicate_ref_by_name("ItemDef").unwrap(),
+            crafted_item_key: defs.predicate_ref_by_name("CraftedItemKey").unwrap(),
+            transferred_item_key: defs.predicate_ref_by_name("TransferredItemKey").unwrap(),
             item_key: defs.predicate_ref_by_name("ItemKey").unwrap(),
+            subset_of: defs.predicate_ref_by_name("SubsetOf").unwrap(),
+            subset_of_recursive: defs.predicate_ref_by_name("SubsetOfRecursive").unwrap(),
+            transferred_ingredients: defs
+                .predicate_ref_by_name("TransferredIngredients")
+                .unwrap(),
+            transferred_item_def: defs.predicate_ref_by_name("TransferredItemDef").unwrap(),
             nullifiers: defs.predicate_ref_by_name("Nullifiers").unwrap(),
             nullifiers_empty: defs.predicate_ref_by_name("NullifiersEmpty").unwrap(),
             nullifiers_recursive: defs.predicate_ref_by_name("NullifiersRecursive").unwrap(),
             commit_creation: defs.predicate_ref_by_name("CommitCreation").unwrap(),
+            commit_destruction: defs.predicate_ref_by_name("CommitDestruction").unwrap(),
+            commit_transfer: defs.predicate_ref_by_name("CommitTransfer").unwrap(),
             defs,
         }
     }
@@ -124,6 +207,6 @@ mod tests {
     fn test_compile_custom_predicates() {
         let params = Params::default();
         let commit_preds = CommitPredicates::compile(&params);
-        assert!(commit_preds.defs.batches.len() == 2);
+        assert!(commit_preds.defs.batches.len() == 4);
     }
 }
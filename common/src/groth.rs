@@ -4,6 +4,7 @@
 use std::time::Instant;
 
 use anyhow::Result;
+use pod2::middleware::F;
 use tracing::info;
 
 const INPUT_PATH: &str = "../tmp/plonky2-proof";
@@ -21,6 +22,15 @@ pub fn load_vk() -> Result<()> {
     Ok(())
 }
 
+/// verifies the Groth16 proof against the verifying key loaded with `load_vk`,
+/// where `public_inputs` are the public inputs of the plonky2 proof verified
+/// inside the Groth16 circuit.
+pub fn verify(proof: Vec<u8>, public_inputs: Vec<F>) -> Result<()> {
+    let pub_inp_bytes = pod2_onchain::encode_public_inputs_gnark(public_inputs);
+    pod2_onchain::groth16_verify(proof, pub_inp_bytes)?;
+    Ok(())
+}
+
 /// computes the one extra recursive proof from the given MainPod's proof in
 /// order to shrink it, together with using the bn254's poseidon variant in the
 /// configuration of the plonky2 prover, in order to make it compatible with the
@@ -18,9 +18,13 @@ pub mod shrink;
 
 #[cfg(not(feature = "groth16"))]
 pub mod groth {
-    use anyhow::Result;
+    use anyhow::{Result, bail};
+    use pod2::middleware::F;
     pub fn load_vk() -> Result<()> {
-        panic!("groth16 disabled");
+        bail!("groth16 disabled");
+    }
+    pub fn verify(_proof: Vec<u8>, _public_inputs: Vec<F>) -> Result<()> {
+        bail!("groth16 disabled");
     }
 }
 
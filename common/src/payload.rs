@@ -1,6 +1,5 @@
-use std::io::{Read, Write};
+use std::io::Write;
 
-use anyhow::{Result, anyhow};
 use plonky2::{
     field::types::{Field, Field64, PrimeField64},
     plonk::proof::CompressedProof,
@@ -10,6 +9,103 @@ use pod2::middleware::{C, CommonCircuitData, D, F, RawValue};
 
 use crate::ProofType;
 
+/// Maximum number of bytes that fit in a blob with the simple encoding: every field element
+/// carries 31 bytes of data and the first one holds the length.
+pub const MAX_PAYLOAD_LEN: usize = (4096 - 1) * 31;
+
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum PayloadError {
+    #[error("invalid payload magic: {0:04x}")]
+    BadMagic(u16),
+    #[error("truncated payload while reading {0}")]
+    Truncated(&'static str),
+    #[error("unknown proof type: {0}")]
+    UnknownProofType(u8),
+    #[error("oversize {what}: {len} > {max}")]
+    Oversize {
+        what: &'static str,
+        len: u64,
+        max: usize,
+    },
+    #[error("{0} trailing bytes after payload")]
+    TrailingBytes(usize),
+    #[error("invalid field element: {0} >= F::ORDER")]
+    InvalidFieldElement(u64),
+    #[error("invalid plonky2 proof: {0}")]
+    InvalidProof(String),
+    #[error("unknown payload version: {0}")]
+    UnknownVersion(u8),
+    #[error("invalid varint while reading {0}")]
+    InvalidVarint(&'static str),
+    #[error("expected a single payload, found {0}")]
+    NotSingle(usize),
+    #[error("unknown payload kind: {0}")]
+    UnknownKind(u8),
+}
+
+/// Bounds-checked reader over the payload bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize, what: &'static str) -> Result<&'a [u8], PayloadError> {
+        if len > self.bytes.len() {
+            return Err(PayloadError::Truncated(what));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn read_array<const N: usize>(&mut self, what: &'static str) -> Result<[u8; N], PayloadError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N, what)?);
+        Ok(array)
+    }
+
+    fn read_u8(&mut self, what: &'static str) -> Result<u8, PayloadError> {
+        Ok(self.read_array::<1>(what)?[0])
+    }
+
+    fn read_elems<const N: usize>(&mut self, what: &'static str) -> Result<[F; N], PayloadError> {
+        let mut elems = [F::ZERO; N];
+        for elem in elems.iter_mut() {
+            let n = u64::from_le_bytes(self.read_array(what)?);
+            if n >= F::ORDER {
+                return Err(PayloadError::InvalidFieldElement(n));
+            }
+            *elem = F::from_canonical_u64(n);
+        }
+        Ok(elems)
+    }
+
+    /// Reads an unsigned LEB128 varint, rejecting overlong encodings.
+    fn read_varint(&mut self, what: &'static str) -> Result<u64, PayloadError> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_u8(what)?;
+            let bits = (byte & 0x7f) as u64;
+            if (shift == 63 && bits > 1) || (shift > 0 && byte == 0) {
+                return Err(PayloadError::InvalidVarint(what));
+            }
+            n |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(PayloadError::InvalidVarint(what))
+    }
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buffer.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buffer.push(n as u8);
+}
+
 pub fn write_elems<const N: usize>(bytes: &mut Vec<u8>, elems: &[F; N]) {
     for elem in elems {
         bytes
@@ -18,78 +114,184 @@ pub fn write_elems<const N: usize>(bytes: &mut Vec<u8>, elems: &[F; N]) {
     }
 }
 
-pub fn read_elems<const N: usize>(bytes: &mut impl Read) -> Result<[F; N]> {
-    let mut elems = [F::ZERO; N];
-    let mut elem_bytes = [0; 8];
-    #[allow(clippy::needless_range_loop)]
-    for i in 0..N {
-        bytes.read_exact(&mut elem_bytes)?;
-        let n = u64::from_le_bytes(elem_bytes);
-        if n >= F::ORDER {
-            return Err(anyhow!("{n} >= F::ORDER"));
+pub fn read_elems<const N: usize>(bytes: &mut &[u8]) -> Result<[F; N], PayloadError> {
+    let mut reader = Reader { bytes };
+    let elems = reader.read_elems("field elements")?;
+    *bytes = reader.bytes;
+    Ok(elems)
+}
+
+/// Operation committed by a payload, which determines the statement its proof is checked
+/// against
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PayloadKind {
+    /// `CommitCreation(item, nullifiers, created_items)`
+    Creation { item: RawValue },
+    /// `CommitDestruction(nullifiers, created_items)`, which only publishes the nullifiers
+    Destruction,
+    /// `CommitTransfer(item, nullifiers, created_items)`
+    Transfer { item: RawValue },
+}
+
+impl PayloadKind {
+    /// Returns the created item, or `None` for a destruction
+    pub fn item(&self) -> Option<RawValue> {
+        match self {
+            Self::Creation { item } | Self::Transfer { item } => Some(*item),
+            Self::Destruction => None,
         }
-        elems[i] = F::from_canonical_u64(n);
     }
-    Ok(elems)
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[allow(clippy::large_enum_variant)]
 pub struct Payload {
     pub proof: PayloadProof,
-    pub item: RawValue,
+    pub kind: PayloadKind,
     pub created_items_root: RawValue,
     pub nullifiers: Vec<RawValue>,
 }
 
 const PAYLOAD_MAGIC: u16 = 0xd10b;
 
+/// Version byte following the magic.  Version 1 payloads have no version byte: the proof type
+/// (0 or 1) follows the magic directly, so explicit versions start at 2.
+///
+/// - v1: `magic ++ proof ++ item ++ created_items_root ++ u8 count ++ nullifiers`
+/// - v2: `magic ++ 2 ++ varint count ++ (proof ++ item ++ created_items_root ++ varint count ++
+///   nullifiers)*`
+/// - v3: `magic ++ 3 ++ varint count ++ (kind ++ proof ++ [item] ++ created_items_root ++
+///   varint count ++ nullifiers)*`, where the item is only present in creations and transfers
+const PAYLOAD_VERSION_2: u8 = 2;
+const PAYLOAD_VERSION_3: u8 = 3;
+
+/// Kind byte of v3 payloads
+const PAYLOAD_KIND_CREATION: u8 = 0;
+const PAYLOAD_KIND_DESTRUCTION: u8 = 1;
+const PAYLOAD_KIND_TRANSFER: u8 = 2;
+
 impl Payload {
+    /// Encodes a single payload.  Equivalent to `Payload::encode_batch(&[payload])`.
     pub fn to_bytes(&self) -> Vec<u8> {
+        Self::encode_batch(std::slice::from_ref(self))
+    }
+
+    /// Decodes a blob containing exactly one payload, in any supported version.
+    pub fn from_bytes(bytes: &[u8], common_data: &CommonCircuitData) -> Result<Self, PayloadError> {
+        let mut payloads = Self::decode_batch(bytes, common_data)?;
+        if payloads.len() != 1 {
+            return Err(PayloadError::NotSingle(payloads.len()));
+        }
+        Ok(payloads.pop().expect("len is 1"))
+    }
+
+    /// Encodes several payloads in a single v3 blob.
+    pub fn encode_batch(payloads: &[Payload]) -> Vec<u8> {
         let mut buffer = Vec::new();
         buffer
             .write_all(&PAYLOAD_MAGIC.to_le_bytes())
             .expect("vec write");
-        self.proof.write_bytes(&mut buffer);
-        write_elems(&mut buffer, &self.item.0);
-        write_elems(&mut buffer, &self.created_items_root.0);
-        assert!(self.nullifiers.len() <= 255);
-        buffer
-            .write_all(&(self.nullifiers.len() as u8).to_le_bytes())
-            .expect("vec write");
-        for nullifier in &self.nullifiers {
-            write_elems(&mut buffer, &nullifier.0);
+        buffer.push(PAYLOAD_VERSION_3);
+        write_varint(&mut buffer, payloads.len() as u64);
+        for payload in payloads {
+            buffer.push(match payload.kind {
+                PayloadKind::Creation { .. } => PAYLOAD_KIND_CREATION,
+                PayloadKind::Destruction => PAYLOAD_KIND_DESTRUCTION,
+                PayloadKind::Transfer { .. } => PAYLOAD_KIND_TRANSFER,
+            });
+            payload.proof.write_bytes(&mut buffer);
+            if let Some(item) = &payload.kind.item() {
+                write_elems(&mut buffer, &item.0);
+            }
+            write_elems(&mut buffer, &payload.created_items_root.0);
+            write_varint(&mut buffer, payload.nullifiers.len() as u64);
+            for nullifier in &payload.nullifiers {
+                write_elems(&mut buffer, &nullifier.0);
+            }
         }
         buffer
     }
 
-    pub fn from_bytes(bytes: &[u8], common_data: &CommonCircuitData) -> Result<Self> {
-        let mut bytes = bytes;
-        let magic = {
-            let mut buffer = [0; 2];
-            bytes.read_exact(&mut buffer)?;
-            u16::from_le_bytes(buffer)
-        };
+    /// Decodes the payloads of a blob, which must span all of `bytes`.  Accepts v1 (single
+    /// payload), v2 and v3 blobs.  Never panics on malformed input.
+    pub fn decode_batch(
+        bytes: &[u8],
+        common_data: &CommonCircuitData,
+    ) -> Result<Vec<Self>, PayloadError> {
+        if bytes.len() > MAX_PAYLOAD_LEN {
+            return Err(PayloadError::Oversize {
+                what: "payload",
+                len: bytes.len() as u64,
+                max: MAX_PAYLOAD_LEN,
+            });
+        }
+        let mut reader = Reader { bytes };
+        let magic = u16::from_le_bytes(reader.read_array("magic")?);
         if magic != PAYLOAD_MAGIC {
-            return Err(anyhow!("Invalid payload magic: {magic:04x}"));
+            return Err(PayloadError::BadMagic(magic));
         }
 
-        let (proof, len) = PayloadProof::from_bytes(bytes, common_data)?;
-        bytes = &bytes[len..];
-        let item = RawValue(read_elems(&mut bytes)?);
-        let created_items_root = RawValue(read_elems(&mut bytes)?);
-        let nullifiers_len = {
-            let mut buffer = [0; 1];
-            bytes.read_exact(&mut buffer)?;
-            u8::from_le_bytes(buffer)
+        let version = *reader
+            .bytes
+            .first()
+            .ok_or(PayloadError::Truncated("version"))?;
+        let payloads = if ProofType::from_byte(&version).is_ok() {
+            vec![Self::read(&mut reader, common_data, 1)?]
+        } else if version == PAYLOAD_VERSION_2 || version == PAYLOAD_VERSION_3 {
+            reader.read_u8("version")?;
+            let count = reader.read_varint("payloads count")?;
+            let mut payloads = Vec::new();
+            for _ in 0..count {
+                payloads.push(Self::read(&mut reader, common_data, version)?);
+            }
+            payloads
+        } else {
+            return Err(PayloadError::UnknownVersion(version));
+        };
+        if !reader.bytes.is_empty() {
+            return Err(PayloadError::TrailingBytes(reader.bytes.len()));
+        }
+        Ok(payloads)
+    }
+
+    /// Reads one payload in the layout of `version` (1, `PAYLOAD_VERSION_2` or
+    /// `PAYLOAD_VERSION_3`).
+    fn read(
+        reader: &mut Reader,
+        common_data: &CommonCircuitData,
+        version: u8,
+    ) -> Result<Self, PayloadError> {
+        let kind = if version == PAYLOAD_VERSION_3 {
+            reader.read_u8("payload kind")?
+        } else {
+            PAYLOAD_KIND_CREATION
+        };
+        if kind > PAYLOAD_KIND_TRANSFER {
+            return Err(PayloadError::UnknownKind(kind));
+        }
+        let proof = PayloadProof::read(reader, common_data)?;
+        let kind = match kind {
+            PAYLOAD_KIND_CREATION => PayloadKind::Creation {
+                item: RawValue(reader.read_elems("item")?),
+            },
+            PAYLOAD_KIND_DESTRUCTION => PayloadKind::Destruction,
+            _ => PayloadKind::Transfer {
+                item: RawValue(reader.read_elems("item")?),
+            },
         };
-        let mut nullifiers = Vec::with_capacity(nullifiers_len as usize);
+        let created_items_root = RawValue(reader.read_elems("created_items_root")?);
+        let nullifiers_len = if version == 1 {
+            reader.read_u8("nullifiers length")? as u64
+        } else {
+            reader.read_varint("nullifiers length")?
+        };
+        let mut nullifiers = Vec::new();
         for _ in 0..nullifiers_len {
-            nullifiers.push(RawValue(read_elems(&mut bytes)?));
+            nullifiers.push(RawValue(reader.read_elems("nullifier")?));
         }
         Ok(Self {
             proof,
-            item,
+            kind,
             created_items_root,
             nullifiers,
         })
@@ -120,38 +322,53 @@ impl PayloadProof {
                     .write_all(&[ProofType::Groth16.to_byte()])
                     .expect("byte write");
                 buffer
-                    .write_all(&b.len().to_le_bytes())
+                    .write_all(&(b.len() as u64).to_le_bytes())
                     .expect("g16 proof bytes length write");
                 buffer.write_all(b).expect("g16 proof bytes write");
             }
         }
     }
-    pub fn from_bytes(bytes: &[u8], common_data: &CommonCircuitData) -> Result<(Self, usize)> {
-        let proof_type = ProofType::from_byte(&bytes[0])?;
-        let bytes = &bytes[1..];
-        let (proof, len): (Self, usize) = match proof_type {
+
+    /// Decodes a proof from the beginning of `bytes`, returning it together with the number of
+    /// bytes read.
+    pub fn from_bytes(
+        bytes: &[u8],
+        common_data: &CommonCircuitData,
+    ) -> Result<(Self, usize), PayloadError> {
+        let mut reader = Reader { bytes };
+        let proof = Self::read(&mut reader, common_data)?;
+        Ok((proof, bytes.len() - reader.bytes.len()))
+    }
+
+    fn read(reader: &mut Reader, common_data: &CommonCircuitData) -> Result<Self, PayloadError> {
+        let proof_type_byte = reader.read_u8("proof type")?;
+        let proof_type = ProofType::from_byte(&proof_type_byte)
+            .map_err(|_| PayloadError::UnknownProofType(proof_type_byte))?;
+        match proof_type {
             ProofType::Plonky2 => {
-                let mut buffer = Buffer::new(bytes);
+                let mut buffer = Buffer::new(reader.bytes);
                 let proof = plonky2::util::serialization::Read::read_compressed_proof(
                     &mut buffer,
                     common_data,
                 )
-                .map_err(|e| anyhow!("read_compressed_proof: {e}"))?;
+                .map_err(|e| PayloadError::InvalidProof(format!("read_compressed_proof: {e}")))?;
                 let len = buffer.pos();
-                (PayloadProof::Plonky2(Box::new(proof)), len)
+                reader.take(len, "plonky2 proof")?;
+                Ok(PayloadProof::Plonky2(Box::new(proof)))
             }
             ProofType::Groth16 => {
-                // get the length
-                let len_bytes: [u8; 8] = bytes[0..8].try_into()?;
-                let len: usize = u64::from_le_bytes(len_bytes) as usize;
-                // return the rest of bytes of the Groth16 proof
-                (PayloadProof::Groth16(bytes[8..8 + len].to_vec()), 8 + len)
+                let len = u64::from_le_bytes(reader.read_array("groth16 proof length")?);
+                if len > MAX_PAYLOAD_LEN as u64 {
+                    return Err(PayloadError::Oversize {
+                        what: "groth16 proof",
+                        len,
+                        max: MAX_PAYLOAD_LEN,
+                    });
+                }
+                let proof = reader.take(len as usize, "groth16 proof")?;
+                Ok(PayloadProof::Groth16(proof.to_vec()))
             }
-        };
-
-        // len+1 because at the beginning we used the first byte for the
-        // proof_type
-        Ok((proof, len + 1))
+        }
     }
 }
 
@@ -159,7 +376,10 @@ impl PayloadProof {
 mod tests {
     use std::collections::HashSet;
 
-    use plonky2::plonk::proof::CompressedProofWithPublicInputs;
+    use plonky2::plonk::{
+        circuit_builder::CircuitBuilder, circuit_data::CircuitConfig,
+        proof::CompressedProofWithPublicInputs,
+    };
     use pod2::{
         backends::plonky2::{
             basetypes::DEFAULT_VD_SET,
@@ -168,6 +388,7 @@ mod tests {
         frontend::{MainPodBuilder, Operation},
         middleware::{Params, Statement, Value, containers::Set},
     };
+    use rand::{Rng, SeedableRng, rngs::StdRng};
 
     use super::*;
     use crate::shrink::{ShrunkMainPodSetup, shrink_compress_pod};
@@ -237,7 +458,7 @@ mod tests {
 
             Payload {
                 proof: PayloadProof::Plonky2(Box::new(shrunk_main_pod_proof.clone())),
-                item: item.raw(),
+                kind: PayloadKind::Creation { item: item.raw() },
                 created_items_root: created_items.raw(),
                 nullifiers,
             }
@@ -260,7 +481,7 @@ mod tests {
         let st = Statement::Custom(
             pred,
             vec![
-                Value::from(payload.item),
+                Value::from(payload.kind.item().unwrap()),
                 nullifiers_set,
                 Value::from(payload.created_items_root),
             ],
@@ -288,4 +509,319 @@ mod tests {
             .unwrap();
         shrunk_main_pod_build.circuit_data.verify(proof).unwrap();
     }
+
+    const FUZZ_ITERS: usize = 256;
+
+    // Circuit data of a tiny circuit, only used to parse plonky2 proofs in the malformed inputs.
+    fn dummy_common_data() -> CommonCircuitData {
+        let builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        builder.build::<C>().common
+    }
+
+    fn random_raw_value(rng: &mut StdRng) -> RawValue {
+        RawValue(std::array::from_fn(|_| {
+            F::from_canonical_u64(rng.random_range(0..F::ORDER))
+        }))
+    }
+
+    fn random_groth16_payload(rng: &mut StdRng) -> Payload {
+        let proof_len = rng.random_range(0..2048);
+        let nullifiers_len = rng.random_range(0..=255);
+        Payload {
+            proof: PayloadProof::Groth16((0..proof_len).map(|_| rng.random()).collect()),
+            kind: PayloadKind::Creation {
+                item: random_raw_value(rng),
+            },
+            created_items_root: random_raw_value(rng),
+            nullifiers: (0..nullifiers_len).map(|_| random_raw_value(rng)).collect(),
+        }
+    }
+
+    fn random_groth16_destruction(rng: &mut StdRng) -> Payload {
+        Payload {
+            kind: PayloadKind::Destruction,
+            ..random_groth16_payload(rng)
+        }
+    }
+
+    fn random_groth16_transfer(rng: &mut StdRng) -> Payload {
+        Payload {
+            kind: PayloadKind::Transfer {
+                item: random_raw_value(rng),
+            },
+            ..random_groth16_payload(rng)
+        }
+    }
+
+    // Encodes a creation payload in the legacy v1 layout.
+    fn to_bytes_v1(payload: &Payload) -> Vec<u8> {
+        let mut buffer = PAYLOAD_MAGIC.to_le_bytes().to_vec();
+        payload.proof.write_bytes(&mut buffer);
+        write_elems(&mut buffer, &payload.kind.item().expect("creation").0);
+        write_elems(&mut buffer, &payload.created_items_root.0);
+        buffer.push(payload.nullifiers.len() as u8);
+        for nullifier in &payload.nullifiers {
+            write_elems(&mut buffer, &nullifier.0);
+        }
+        buffer
+    }
+
+    // Encodes creation payloads in the legacy v2 layout.
+    fn encode_batch_v2(payloads: &[Payload]) -> Vec<u8> {
+        let mut buffer = PAYLOAD_MAGIC.to_le_bytes().to_vec();
+        buffer.push(PAYLOAD_VERSION_2);
+        write_varint(&mut buffer, payloads.len() as u64);
+        for payload in payloads {
+            payload.proof.write_bytes(&mut buffer);
+            write_elems(&mut buffer, &payload.kind.item().expect("creation").0);
+            write_elems(&mut buffer, &payload.created_items_root.0);
+            write_varint(&mut buffer, payload.nullifiers.len() as u64);
+            for nullifier in &payload.nullifiers {
+                write_elems(&mut buffer, &nullifier.0);
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_payload_groth16_roundtrip() {
+        let common_data = dummy_common_data();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..FUZZ_ITERS {
+            let payload = random_groth16_payload(&mut rng);
+            let bytes = payload.to_bytes();
+            assert_eq!(
+                Payload::from_bytes(&bytes, &common_data),
+                Ok(payload.clone())
+            );
+            let bytes_v1 = to_bytes_v1(&payload);
+            assert_eq!(
+                Payload::from_bytes(&bytes_v1, &common_data),
+                Ok(payload.clone())
+            );
+            let bytes_v2 = encode_batch_v2(std::slice::from_ref(&payload));
+            assert_eq!(Payload::from_bytes(&bytes_v2, &common_data), Ok(payload));
+
+            let destruction = random_groth16_destruction(&mut rng);
+            let bytes = destruction.to_bytes();
+            assert_eq!(Payload::from_bytes(&bytes, &common_data), Ok(destruction));
+
+            let transfer = random_groth16_transfer(&mut rng);
+            let bytes = transfer.to_bytes();
+            assert_eq!(Payload::from_bytes(&bytes, &common_data), Ok(transfer));
+        }
+    }
+
+    #[test]
+    fn test_payload_batch_roundtrip() {
+        let common_data = dummy_common_data();
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..16 {
+            let count = rng.random_range(0..16);
+            let payloads: Vec<_> = (0..count)
+                .map(|_| random_groth16_payload(&mut rng))
+                .collect();
+            let bytes = encode_batch_v2(&payloads);
+            assert_eq!(
+                Payload::decode_batch(&bytes, &common_data),
+                Ok(payloads.clone())
+            );
+
+            // Mix in destructions and transfers, which need v3
+            let payloads: Vec<_> = payloads
+                .into_iter()
+                .map(|payload| match rng.random_range(0..3) {
+                    0 => payload,
+                    1 => Payload {
+                        kind: PayloadKind::Destruction,
+                        ..payload
+                    },
+                    _ => Payload {
+                        kind: PayloadKind::Transfer {
+                            item: random_raw_value(&mut rng),
+                        },
+                        ..payload
+                    },
+                })
+                .collect();
+            let bytes = Payload::encode_batch(&payloads);
+            assert_eq!(Payload::decode_batch(&bytes, &common_data), Ok(payloads));
+        }
+
+        // More than 255 nullifiers don't fit in v1
+        let mut payload = random_groth16_payload(&mut rng);
+        payload.nullifiers = (0..1000).map(|_| random_raw_value(&mut rng)).collect();
+        let bytes = payload.to_bytes();
+        assert_eq!(
+            Payload::from_bytes(&bytes, &common_data),
+            Ok(payload.clone())
+        );
+
+        let bytes = Payload::encode_batch(&[payload.clone(), payload]);
+        assert_eq!(
+            Payload::from_bytes(&bytes, &common_data),
+            Err(PayloadError::NotSingle(2))
+        );
+    }
+
+    #[test]
+    fn test_varint() {
+        for n in [0, 1, 0x7f, 0x80, 300, u32::MAX as u64, u64::MAX] {
+            let mut buffer = Vec::new();
+            write_varint(&mut buffer, n);
+            let mut reader = Reader { bytes: &buffer };
+            assert_eq!(reader.read_varint("n"), Ok(n));
+            assert!(reader.bytes.is_empty());
+        }
+
+        let invalid: [&[u8]; 3] = [
+            // Overlong encoding of 0
+            &[0x80, 0x00],
+            // Overflows u64
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02],
+            &[0xff; 11],
+        ];
+        for bytes in invalid {
+            let mut reader = Reader { bytes };
+            assert_eq!(
+                reader.read_varint("n"),
+                Err(PayloadError::InvalidVarint("n"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_payload_truncated() {
+        let common_data = dummy_common_data();
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..16 {
+            let payload = random_groth16_payload(&mut rng);
+            let destruction = random_groth16_destruction(&mut rng);
+            let transfer = random_groth16_transfer(&mut rng);
+            for bytes in [
+                payload.to_bytes(),
+                to_bytes_v1(&payload),
+                encode_batch_v2(std::slice::from_ref(&payload)),
+                destruction.to_bytes(),
+                transfer.to_bytes(),
+            ] {
+                for len in 0..bytes.len() {
+                    assert!(matches!(
+                        Payload::from_bytes(&bytes[..len], &common_data),
+                        Err(PayloadError::Truncated(_))
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_payload_malformed() {
+        let common_data = dummy_common_data();
+        let mut rng = StdRng::seed_from_u64(2);
+        let payload = random_groth16_payload(&mut rng);
+        let bytes = to_bytes_v1(&payload);
+
+        let mut trailing = bytes.clone();
+        trailing.extend_from_slice(&[0, 1, 2]);
+        assert_eq!(
+            Payload::from_bytes(&trailing, &common_data),
+            Err(PayloadError::TrailingBytes(3))
+        );
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[..2].copy_from_slice(&0xbeefu16.to_le_bytes());
+        assert_eq!(
+            Payload::from_bytes(&bad_magic, &common_data),
+            Err(PayloadError::BadMagic(0xbeef))
+        );
+
+        let mut bad_version = bytes.clone();
+        bad_version[2] = 0xff;
+        assert_eq!(
+            Payload::from_bytes(&bad_version, &common_data),
+            Err(PayloadError::UnknownVersion(0xff))
+        );
+
+        // v3: magic ++ version ++ count ++ kind ++ proof type
+        let mut bad_kind = payload.to_bytes();
+        bad_kind[4] = 0xff;
+        assert_eq!(
+            Payload::from_bytes(&bad_kind, &common_data),
+            Err(PayloadError::UnknownKind(0xff))
+        );
+
+        let mut bad_proof_type = payload.to_bytes();
+        bad_proof_type[5] = 0xff;
+        assert_eq!(
+            Payload::from_bytes(&bad_proof_type, &common_data),
+            Err(PayloadError::UnknownProofType(0xff))
+        );
+
+        let mut oversize_proof = bytes.clone();
+        oversize_proof[3..11].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            Payload::from_bytes(&oversize_proof, &common_data),
+            Err(PayloadError::Oversize { len: u64::MAX, .. })
+        ));
+
+        let mut long_proof = bytes.clone();
+        long_proof[3..11].copy_from_slice(&(MAX_PAYLOAD_LEN as u64).to_le_bytes());
+        assert!(matches!(
+            Payload::from_bytes(&long_proof, &common_data),
+            Err(PayloadError::Truncated(_))
+        ));
+
+        let oversize = [
+            PAYLOAD_MAGIC.to_le_bytes().to_vec(),
+            vec![0; MAX_PAYLOAD_LEN],
+        ]
+        .concat();
+        assert!(matches!(
+            Payload::from_bytes(&oversize, &common_data),
+            Err(PayloadError::Oversize { .. })
+        ));
+
+        let mut bad_elem = bytes.clone();
+        let proof_len = u64::from_le_bytes(bytes[3..11].try_into().unwrap()) as usize;
+        let item_offset = 11 + proof_len;
+        bad_elem[item_offset..item_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            Payload::from_bytes(&bad_elem, &common_data),
+            Err(PayloadError::InvalidFieldElement(u64::MAX))
+        );
+    }
+
+    // Decoding arbitrary bytes must return an error instead of panicking.
+    #[test]
+    fn test_payload_fuzz() {
+        let common_data = dummy_common_data();
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..FUZZ_ITERS {
+            // Mutated valid payloads, in all versions
+            let payloads = [
+                random_groth16_payload(&mut rng),
+                random_groth16_payload(&mut rng),
+            ];
+            let mixed = [payloads[0].clone(), random_groth16_destruction(&mut rng)];
+            for mut bytes in [
+                Payload::encode_batch(&mixed),
+                encode_batch_v2(&payloads),
+                to_bytes_v1(&payloads[0]),
+            ] {
+                for _ in 0..rng.random_range(1..8) {
+                    let index = rng.random_range(0..bytes.len());
+                    bytes[index] = rng.random();
+                }
+                let _ = Payload::decode_batch(&bytes, &common_data);
+            }
+
+            // Random bytes after a valid header, for all proof types and versions
+            let len = rng.random_range(0..4096);
+            let mut bytes = PAYLOAD_MAGIC.to_le_bytes().to_vec();
+            bytes.push(rng.random_range(0..=4));
+            bytes.extend((0..len).map(|_| rng.random::<u8>()));
+            let _ = Payload::decode_batch(&bytes, &common_data);
+        }
+    }
 }
@@ -1,18 +0,0 @@
-use pod2::middleware::{EMPTY_VALUE, RawValue};
-
-pub const STONE_BLUEPRINT: &str = "stone";
-pub const STONE_MINING_MAX: u64 = 0x0020_0000_0000_0000;
-pub const STONE_WORK: RawValue = EMPTY_VALUE;
-
-pub const WOOD_BLUEPRINT: &str = "wood";
-pub const WOOD_MINING_MAX: u64 = 0x0020_0000_0000_0000;
-pub const WOOD_WORK: RawValue = EMPTY_VALUE;
-
-pub const AXE_BLUEPRINT: &str = "axe";
-pub const AXE_MINING_MAX: u64 = 0x0020_0000_0000_0000;
-// TODO
-pub const AXE_WORK: RawValue = EMPTY_VALUE;
-
-pub const WOODEN_AXE_BLUEPRINT: &str = "wooden-axe";
-pub const WOODEN_AXE_MINING_MAX: u64 = 0x0020_0000_0000_0000;
-pub const WOODEN_AXE_WORK: RawValue = EMPTY_VALUE;
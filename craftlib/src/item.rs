@@ -1,11 +1,25 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    thread,
+};
 
+use anyhow::{anyhow, bail};
 use commitlib::{IngredientsDef, ItemDef};
 use log;
-use pod2::middleware::{EMPTY_VALUE, Hash, Params, RawValue, Statement, ToFields, Value};
-use pod2utils::{macros::BuildContext, set, st_custom};
+use pod2::{
+    frontend::Operation,
+    middleware::{EMPTY_VALUE, Hash, Params, RawValue, Statement, ToFields, Value},
+};
+use pod2utils::{
+    macros::{BuildContext, find_custom_pred_by_name},
+    op, set,
+};
 
-use crate::constants::{AXE_BLUEPRINT, STONE_BLUEPRINT, WOOD_BLUEPRINT, WOODEN_AXE_BLUEPRINT};
+use crate::recipes::{INPUTS_PER_PREDICATE, RecipeDef};
+
+// Number of seeds each mining thread tries between progress reports
+const MINING_PROGRESS_INTERVAL: u64 = 1 << 12;
 
 // Reusable recipe for an item to be mined, not including the variable
 // cryptographic values.
@@ -35,18 +49,87 @@ impl MiningRecipe {
         start_seed: i64,
         mine_max: u64,
     ) -> pod2::middleware::Result<Option<IngredientsDef>> {
-        log::info!("Mining...");
-        for seed in start_seed..=i64::MAX {
-            let ingredients = self.prep_ingredients(key, seed);
-            let ingredients_hash = ingredients.hash(params)?;
-            let mining_val = ingredients_hash.to_fields(params)[0];
-            if mining_val.0 <= mine_max {
-                log::info!("Mining complete!");
-                return Ok(Some(ingredients));
+        let cancel = AtomicBool::new(false);
+        self.do_mining_parallel(params, key, start_seed, mine_max, 1, &cancel, &|_| {})
+    }
+
+    // Mines with `num_threads` threads, where thread `i` tries the seeds
+    // `start_seed + i + k * num_threads`.  All threads stop as soon as one of
+    // them finds a match or fails, so with more than one thread the mined seed
+    // is not necessarily the lowest one.  Setting `cancel` stops the mining
+    // without a match.  `progress` is called periodically with the total number
+    // of seeds tried so far.
+    #[allow(clippy::too_many_arguments)]
+    pub fn do_mining_parallel(
+        &self,
+        params: &Params,
+        key: RawValue,
+        start_seed: i64,
+        mine_max: u64,
+        num_threads: usize,
+        cancel: &AtomicBool,
+        progress: &(dyn Fn(u64) + Sync),
+    ) -> pod2::middleware::Result<Option<IngredientsDef>> {
+        let num_threads = num_threads.max(1);
+        log::info!("Mining with {num_threads} threads...");
+        let stop = AtomicBool::new(false);
+        let tried = AtomicU64::new(0);
+        let results = thread::scope(|scope| {
+            let workers: Vec<_> = (0..num_threads)
+                .map(|i| {
+                    let (stop, tried) = (&stop, &tried);
+                    scope.spawn(move || -> pod2::middleware::Result<_> {
+                        let mine = || -> pod2::middleware::Result<_> {
+                            let mut seed = start_seed.checked_add(i as i64);
+                            let mut untallied = 0;
+                            while let Some(s) = seed
+                                && !stop.load(Ordering::Relaxed)
+                                && !cancel.load(Ordering::Relaxed)
+                            {
+                                let ingredients = self.prep_ingredients(key, s);
+                                let ingredients_hash = ingredients.hash(params)?;
+                                let mining_val = ingredients_hash.to_fields(params)[0];
+                                if mining_val.0 <= mine_max {
+                                    return Ok(Some(ingredients));
+                                }
+                                untallied += 1;
+                                if untallied == MINING_PROGRESS_INTERVAL {
+                                    progress(
+                                        tried.fetch_add(untallied, Ordering::Relaxed) + untallied,
+                                    );
+                                    untallied = 0;
+                                }
+                                seed = s.checked_add(num_threads as i64);
+                            }
+                            Ok(None)
+                        };
+                        let result = mine();
+                        // A match or an error ends the mining of all threads
+                        if !matches!(result, Ok(None)) {
+                            stop.store(true, Ordering::Relaxed);
+                        }
+                        result
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("mining thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        let mut mined = None;
+        for result in results {
+            if let Some(ingredients) = result? {
+                mined.get_or_insert(ingredients);
             }
         }
-
-        Ok(None)
+        if mined.is_some() {
+            log::info!("Mining complete!");
+        } else if cancel.load(Ordering::Relaxed) {
+            log::info!("Mining cancelled");
+        }
+        Ok(mined)
     }
 
     pub fn new(blueprint: String, inputs: &[Hash]) -> Self {
@@ -67,117 +150,134 @@ impl<'a> CraftBuilder<'a> {
         Self { ctx, params }
     }
 
-    // Adds statements to MainPodBilder to represent Stone as additions to
-    // already-existing generic item statements.
-    // Builds the following public predicates: IsStone
-    // Returns the Statement object for IsStone for use in further statements.
-    pub fn st_is_stone(
+    // Builds the statement constraining the work of an item: the Pow statement of its PowPod
+    // for blueprints requiring sequential work, or Equal(work, {}) otherwise.
+    pub fn st_work(
         &mut self,
-        item_def: ItemDef,
-        st_item_def: Statement,
-        st_pow: Statement,
+        item_def: &ItemDef,
+        st_pow: Option<Statement>,
     ) -> anyhow::Result<Statement> {
-        // Build IsStone(item)
-        Ok(st_custom!(self.ctx,
-            IsStone() = (
-                st_item_def,
-                Equal(item_def.ingredients.inputs_set(self.params)?, EMPTY_VALUE),
-                DictContains(item_def.ingredients.dict(self.params)?, "blueprint", STONE_BLUEPRINT),
-                st_pow
-            ))?)
+        Ok(match st_pow {
+            Some(st_pow) => st_pow,
+            None => self
+                .ctx
+                .builder
+                .priv_op(op!(Equal(item_def.work, EMPTY_VALUE)))?,
+        })
     }
 
-    pub fn st_is_wood(
+    // Adds statements to MainPodBuilder to represent an item crafted with
+    // `recipe` as additions to already-existing generic item statements.
+    // `sts_inputs` are the statements of the recipe predicates of the inputs,
+    // in the order of the recipe.
+    // Builds the following private predicates: the recipe predicate, its crafted
+    // branch and the chain of its inputs predicates, if any.
+    // Returns the Statement object for the recipe predicate for use in further
+    // statements.
+    pub fn st_recipe(
         &mut self,
+        recipe: &RecipeDef,
         item_def: ItemDef,
         st_item_def: Statement,
+        st_hash_in_range: Statement,
+        st_work: Statement,
+        sts_inputs: Vec<Statement>,
     ) -> anyhow::Result<Statement> {
-        // Build IsWood(item)
-        Ok(st_custom!(self.ctx,
-            IsWood() = (
+        let st_inputs = if recipe.inputs.is_empty() {
+            self.ctx.builder.priv_op(op!(Equal(
+                item_def.ingredients.inputs_set(self.params)?,
+                EMPTY_VALUE
+            )))?
+        } else {
+            self.st_recipe_inputs(recipe, sts_inputs)?
+        };
+        let st_blueprint = self.ctx.builder.priv_op(op!(DictContains(
+            item_def.ingredients.dict(self.params)?,
+            "blueprint",
+            recipe.blueprint.as_str()
+        )))?;
+        let st_crafted = self.st_custom_by_name(
+            &recipe.crafted_predicate(),
+            vec![
                 st_item_def,
-                Equal(item_def.ingredients.inputs_set(self.params)?, EMPTY_VALUE),
-                DictContains(item_def.ingredients.dict(self.params)?, "blueprint", WOOD_BLUEPRINT),
-                Equal(item_def.work, EMPTY_VALUE)
-            ))?)
+                st_inputs,
+                st_blueprint,
+                st_hash_in_range,
+                st_work,
+            ],
+        )?;
+        self.st_custom_by_name(&recipe.predicate, vec![st_crafted, Statement::None])
     }
 
-    fn st_axe_inputs(
+    // Adds statements to MainPodBuilder to represent the item created by
+    // transferring an item which follows `recipe`, from the TransferredItemDef
+    // statement of the transfer and the recipe predicate statement of the
+    // original item.
+    // Returns the Statement object for the recipe predicate of the new item.
+    pub fn st_recipe_transferred(
         &mut self,
-        st_is_wood: Statement,
-        st_is_stone: Statement,
+        recipe: &RecipeDef,
+        st_transferred_item_def: Statement,
+        st_original_recipe: Statement,
     ) -> anyhow::Result<Statement> {
-        let wood = st_is_wood.args()[0].literal().unwrap();
-        let stone = st_is_stone.args()[0].literal().unwrap();
-        let empty_set = set!(self.params.max_depth_mt_containers).unwrap();
-        let mut s1 = empty_set.clone();
-        s1.insert(&wood).unwrap();
-        let mut inputs = s1.clone();
-        inputs.insert(&stone).unwrap();
-        Ok(st_custom!(self.ctx,
-            AxeInputs() = (
-                SetInsert(s1, empty_set, wood),
-                SetInsert(inputs, s1, stone),
-                st_is_wood,
-                st_is_stone
-            ))?)
+        let st_transferred = self.st_custom_by_name(
+            &recipe.transferred_predicate(),
+            vec![st_transferred_item_def, st_original_recipe],
+        )?;
+        self.st_custom_by_name(&recipe.predicate, vec![Statement::None, st_transferred])
     }
 
-    pub fn st_is_axe(
+    // Builds the chain of inputs predicates of `recipe` from the statements of
+    // the recipe predicates of its inputs, in the order of the recipe.  Each
+    // link proves the set of the previous link extended with the next inputs:
+    // inputs = ({} ∪ {input1} ∪ {input2}) ∪ {input3} ...
+    // Returns the Statement object for the last link, which proves all the
+    // inputs.
+    pub fn st_recipe_inputs(
         &mut self,
-        item_def: ItemDef,
-        st_item_def: Statement,
-        st_is_wood: Statement,
-        st_is_stone: Statement,
+        recipe: &RecipeDef,
+        sts_inputs: Vec<Statement>,
     ) -> anyhow::Result<Statement> {
-        let st_axe_inputs = self.st_axe_inputs(st_is_wood, st_is_stone)?;
-        // Build IsAxe(item)
-        Ok(st_custom!(self.ctx,
-            IsAxe() = (
-                st_item_def,
-                DictContains(item_def.ingredients.dict(self.params)?, "blueprint", AXE_BLUEPRINT),
-                Equal(item_def.work, EMPTY_VALUE),
-                st_axe_inputs
-            ))?)
+        if sts_inputs.is_empty() || sts_inputs.len() != recipe.inputs.len() {
+            bail!(
+                "{} takes {} inputs, got {}",
+                recipe.blueprint,
+                recipe.inputs.len(),
+                sts_inputs.len()
+            );
+        }
+        let mut set = set!(self.params.max_depth_mt_containers)?;
+        let mut st_rest = None;
+        for (predicate, sts_group) in recipe
+            .inputs_predicates()
+            .iter()
+            .zip(sts_inputs.chunks(INPUTS_PER_PREDICATE))
+        {
+            let mut sts: Vec<Statement> = st_rest.take().into_iter().collect();
+            for st_input in sts_group {
+                let input = st_input.args()[0].literal()?;
+                let mut new_set = set.clone();
+                new_set.insert(&input)?;
+                sts.push(
+                    self.ctx
+                        .builder
+                        .priv_op(op!(SetInsert(new_set, set, input)))?,
+                );
+                set = new_set;
+            }
+            sts.extend_from_slice(sts_group);
+            st_rest = Some(self.st_custom_by_name(predicate, sts)?);
+        }
+        Ok(st_rest.expect("at least one input"))
     }
 
-    fn st_wooden_axe_inputs(
-        &mut self,
-        st_is_wood1: Statement,
-        st_is_wood2: Statement,
-    ) -> anyhow::Result<Statement> {
-        let wood1 = st_is_wood1.args()[0].literal().unwrap();
-        let wood2 = st_is_wood2.args()[0].literal().unwrap();
-        let empty_set = set!(self.params.max_depth_mt_containers).unwrap();
-        let mut s1 = empty_set.clone();
-        s1.insert(&wood1).unwrap();
-        let mut inputs = s1.clone();
-        inputs.insert(&wood2).unwrap();
-        Ok(st_custom!(self.ctx,
-            WoodenAxeInputs() = (
-                SetInsert(s1, empty_set, wood1),
-                SetInsert(inputs, s1, wood2),
-                st_is_wood1,
-                st_is_wood2
-            ))?)
-    }
-
-    pub fn st_is_wooden_axe(
-        &mut self,
-        item_def: ItemDef,
-        st_item_def: Statement,
-        st_is_wood1: Statement,
-        st_is_wood2: Statement,
-    ) -> anyhow::Result<Statement> {
-        let st_wooden_axe_inputs = self.st_wooden_axe_inputs(st_is_wood1, st_is_wood2)?;
-        // Build IsWoodenAxe(item)
-        Ok(st_custom!(self.ctx,
-            IsWoodenAxe() = (
-                st_item_def,
-                DictContains(item_def.ingredients.dict(self.params)?, "blueprint", WOODEN_AXE_BLUEPRINT),
-                Equal(item_def.work, EMPTY_VALUE),
-                st_wooden_axe_inputs
-            ))?)
+    fn st_custom_by_name(&mut self, name: &str, sts: Vec<Statement>) -> anyhow::Result<Statement> {
+        let predicate = find_custom_pred_by_name(self.ctx.batches, name)
+            .ok_or_else(|| anyhow!("predicate {name} not found"))?;
+        Ok(self
+            .ctx
+            .builder
+            .op(false, vec![], Operation::custom(predicate, sts))?)
     }
 }
 
@@ -186,7 +286,10 @@ mod tests {
 
     use std::{collections::HashMap, sync::Arc};
 
-    use commitlib::{ItemBuilder, ItemDef, predicates::CommitPredicates, util::set_from_hashes};
+    use commitlib::{
+        ItemBuilder, ItemDef, item_pod::ItemPod, key_commitment, nullifier_of_key,
+        planner::MainPodPlanner, predicates::CommitPredicates, util::set_from_hashes,
+    };
     use pod2::{
         backends::plonky2::mock::mainpod::MockProver,
         frontend::{MainPod, MainPodBuilder},
@@ -199,10 +302,8 @@ mod tests {
 
     use super::*;
     use crate::{
-        constants::{STONE_BLUEPRINT, STONE_MINING_MAX, STONE_WORK},
-        powpod::PowPod,
-        predicates::ItemPredicates,
-        test_util::test::mock_vd_set,
+        powpod::PowPod, predicates::ItemPredicates, rangepod::HashInRangePod,
+        recipes::RecipeRegistry, test_util::test::mock_vd_set,
     };
 
     // Seed of 2612=0xA34 is a match with hash 6647892930992163=0x000A7EE9D427E832.
@@ -210,9 +311,12 @@ mod tests {
 
     // Builds the private POD to store locally for use in further crafting.
     // Contains the following public predicates: ItemDef, ItemKey, IsStone
+    #[allow(clippy::too_many_arguments)]
     fn prove_stone(
+        stone: &RecipeDef,
         item_def: ItemDef,
         pow_pod: MainPod,
+        range_pod: MainPod,
 
         // TODO: All the args below might belong in a ItemBuilder object
         batches: &[Arc<CustomPredicateBatch>],
@@ -228,10 +332,19 @@ mod tests {
         item_builder.ctx.builder.reveal(&st_item_key);
 
         let st_pow = pow_pod.public_statements[0].clone();
+        let st_hash_in_range = range_pod.public_statements[0].clone();
 
         let mut craft_builder = CraftBuilder::new(BuildContext::new(&mut builder, batches), params);
         craft_builder.ctx.builder.add_pod(pow_pod);
-        let st_is_stone = craft_builder.st_is_stone(item_def, st_item_def, st_pow)?;
+        craft_builder.ctx.builder.add_pod(range_pod);
+        let st_is_stone = craft_builder.st_recipe(
+            stone,
+            item_def,
+            st_item_def,
+            st_hash_in_range,
+            st_pow,
+            vec![],
+        )?;
         craft_builder.ctx.builder.reveal(&st_is_stone);
 
         // Prove MainPOD
@@ -244,7 +357,7 @@ mod tests {
     fn prove_st_commit_creation(
         item_def: ItemDef,
         created_items: Set,
-        item_main_pod: MainPod,
+        item_pod: ItemPod,
 
         // TODO: All the args below might belong in a ItemBuilder object
         batches: &[Arc<CustomPredicateBatch>],
@@ -254,9 +367,8 @@ mod tests {
     ) -> anyhow::Result<MainPod> {
         let mut builder = MainPodBuilder::new(&Default::default(), vd_set);
 
-        // TODO: Consider a more robust lookup for this which doesn't depend on index.
-        let st_item_def = item_main_pod.public_statements[0].clone();
-        builder.add_pod(item_main_pod);
+        let st_item_def = item_pod.st_item_def().clone();
+        builder.add_pod(item_pod.into());
 
         let mut item_builder = ItemBuilder::new(BuildContext::new(&mut builder, batches), params);
         let (st_nullifier, _) = item_builder.st_nullifiers(vec![])?;
@@ -268,6 +380,66 @@ mod tests {
         Ok(builder.prove(prover)?)
     }
 
+    // Builds the private POD of an item crafted with `recipe` from the items of
+    // `input_pods`, for recipes without sequential work.
+    // Contains the following public predicates: ItemDef, ItemKey, Nullifiers and
+    // the recipe predicate.
+    fn prove_item(
+        recipe: &RecipeDef,
+        item_def: ItemDef,
+        input_pods: Vec<ItemPod>,
+        batches: &[Arc<CustomPredicateBatch>],
+        params: &Params,
+        prover: &dyn MainPodProver,
+        vd_set: &VDSet,
+    ) -> anyhow::Result<MainPod> {
+        let mut planner = MainPodPlanner::new(params, vd_set);
+        let mut sts_input_item_key = Vec::new();
+        let mut sts_input_craft = Vec::new();
+        for input_pod in input_pods {
+            sts_input_item_key.push(input_pod.st_item_key().clone());
+            sts_input_craft.push(input_pod.st_craft().unwrap().clone());
+            planner.builder().add_pod(input_pod.into());
+        }
+        let range_pod = HashInRangePod::new(
+            params,
+            vd_set.clone(),
+            recipe.mining_max,
+            RawValue::from(item_def.ingredients.hash(params)?),
+        )?;
+        let st_hash_in_range = range_pod.pub_statements()[0].clone();
+        planner.builder().add_pod(MainPod {
+            pod: Box::new(range_pod.clone()),
+            public_statements: range_pod.pub_statements(),
+            params: params.clone(),
+        });
+
+        let mut item_builder =
+            ItemBuilder::new(BuildContext::new(planner.builder(), batches), params);
+        let (st_nullifiers, _) = item_builder.st_nullifiers(sts_input_item_key)?;
+        let st_item_def = item_builder.st_item_def(item_def.clone())?;
+        let st_item_key = item_builder.st_item_key(st_item_def.clone())?;
+
+        let mut craft_builder =
+            CraftBuilder::new(BuildContext::new(planner.builder(), batches), params);
+        let st_work = craft_builder.st_work(&item_def, None)?;
+        let st_craft = craft_builder.st_recipe(
+            recipe,
+            item_def,
+            st_item_def.clone(),
+            st_hash_in_range,
+            st_work,
+            sts_input_craft,
+        )?;
+
+        let builder = planner.builder();
+        builder.reveal(&st_item_key);
+        builder.reveal(&st_item_def);
+        builder.reveal(&st_nullifiers);
+        builder.reveal(&st_craft);
+        Ok(planner.prove(prover)?)
+    }
+
     fn check_matched_wildcards(matched: HashMap<String, Value>, expected: HashMap<String, Value>) {
         assert_eq!(matched.len(), expected.len(), "len");
         for name in expected.keys() {
@@ -278,18 +450,20 @@ mod tests {
     #[test]
     fn test_mine_stone() -> anyhow::Result<()> {
         let params = Params::default();
-        let mining_recipe = MiningRecipe::new(STONE_BLUEPRINT.to_string(), &[]);
+        let recipes = RecipeRegistry::default();
+        let stone = recipes.get("stone").unwrap();
+        let mining_recipe = MiningRecipe::new(stone.blueprint.clone(), &[]);
         let key = RawValue::from(0xBADC0DE);
 
         // Seed of 2612=0xA34 is a match with hash 6647892930992163=0x000A7EE9D427E832.
         // TODO: This test is going to get slower (~2s) whenever the ingredient
         // dict definition changes.  Need a better approach to testing mining.
         let mine_success =
-            mining_recipe.do_mining(&params, key, STONE_START_SEED, STONE_MINING_MAX)?;
+            mining_recipe.do_mining(&params, key, STONE_START_SEED, stone.mining_max)?;
         assert!(mine_success.is_some());
 
         let ingredients_def = mine_success.unwrap();
-        let item_def = ItemDef::new(ingredients_def.clone(), STONE_WORK);
+        let item_def = ItemDef::new(ingredients_def.clone(), EMPTY_VALUE);
         let item_hash = item_def.item_hash(&params)?;
         println!(
             "Mined stone {:?} from ingredients {:?}",
@@ -300,12 +474,72 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_mine_stone_parallel() -> anyhow::Result<()> {
+        let params = Params::default();
+        let recipes = RecipeRegistry::default();
+        let stone = recipes.get("stone").unwrap();
+        let mining_recipe = MiningRecipe::new(stone.blueprint.clone(), &[]);
+        let key = RawValue::from(0xBADC0DE);
+
+        let tried = AtomicU64::new(0);
+        let cancel = AtomicBool::new(false);
+        let mine_success = mining_recipe.do_mining_parallel(
+            &params,
+            key,
+            STONE_START_SEED,
+            stone.mining_max,
+            4,
+            &cancel,
+            &|n| {
+                tried.fetch_max(n, Ordering::Relaxed);
+            },
+        )?;
+
+        let ingredients_def = mine_success.expect("a seed is mined");
+        let mining_val = ingredients_def.hash(&params)?.to_fields(&params)[0];
+        assert!(mining_val.0 <= stone.mining_max);
+        println!(
+            "Mined stone from seed {:?} after {} reported tries",
+            ingredients_def.app_layer["seed"],
+            tried.load(Ordering::Relaxed)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mine_stone_cancelled() -> anyhow::Result<()> {
+        let params = Params::default();
+        let recipes = RecipeRegistry::default();
+        let stone = recipes.get("stone").unwrap();
+        let mining_recipe = MiningRecipe::new(stone.blueprint.clone(), &[]);
+        let key = RawValue::from(0xBADC0DE);
+
+        // No seed is tried once cancelled, not even the matching one
+        let cancel = AtomicBool::new(true);
+        let mine_success = mining_recipe.do_mining_parallel(
+            &params,
+            key,
+            0xA34,
+            stone.mining_max,
+            4,
+            &cancel,
+            &|_| {},
+        )?;
+        assert!(mine_success.is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn test_mine_and_prove_stone() -> anyhow::Result<()> {
         let params = Params::default();
         let commit_preds = CommitPredicates::compile(&params);
         let mut batches = commit_preds.defs.batches.clone();
-        let item_preds = ItemPredicates::compile(&params, &commit_preds);
+        let recipes = RecipeRegistry::default();
+        let stone = recipes.get("stone").unwrap();
+        let item_preds = ItemPredicates::compile(&params, &commit_preds, &recipes);
         batches.extend_from_slice(&item_preds.defs.batches);
 
         let prover = &MockProver {};
@@ -313,15 +547,15 @@ mod tests {
 
         // Mine stone with a selected key.
         let key = RawValue::from(0xBADC0DE);
-        let mining_recipe = MiningRecipe::new(STONE_BLUEPRINT.to_string(), &[]);
+        let mining_recipe = MiningRecipe::new(stone.blueprint.clone(), &[]);
         let ingredients_def = mining_recipe
-            .do_mining(&params, key, STONE_START_SEED, STONE_MINING_MAX)?
+            .do_mining(&params, key, STONE_START_SEED, stone.mining_max)?
             .unwrap();
 
         let pow_pod = PowPod::new(
             &params,
             vd_set.clone(),
-            3, // num_iters
+            stone.pow_iters,
             RawValue::from(ingredients_def.dict(&params)?.commitment()),
         )?;
         let main_pow_pod = MainPod {
@@ -330,6 +564,18 @@ mod tests {
             params: params.clone(),
         };
 
+        let range_pod = HashInRangePod::new(
+            &params,
+            vd_set.clone(),
+            stone.mining_max,
+            RawValue::from(ingredients_def.dict(&params)?.commitment()),
+        )?;
+        let main_range_pod = MainPod {
+            pod: Box::new(range_pod.clone()),
+            public_statements: range_pod.pub_statements(),
+            params: params.clone(),
+        };
+
         // Pre-calculate hashes and intermediate values.
         let ingredients_dict = ingredients_def.dict(&params)?;
         let inputs_set = ingredients_def.inputs_set(&params)?;
@@ -342,8 +588,10 @@ mod tests {
         // Prove a stone POD.  This is the private POD for the player to store
         // locally for future crafting.
         let stone_main_pod = prove_stone(
+            stone,
             item_def.clone(),
             main_pow_pod,
+            main_range_pod,
             &batches,
             &params,
             prover,
@@ -404,10 +652,13 @@ mod tests {
 
         // TODO Prove a commitment POD to send on-chain.  This intentionally doesn't
         // expose any public statements other than CommitCreation.
+        let stone_item_pod = ItemPod::new(stone_main_pod, &commit_preds)?;
+        assert!(stone_item_pod.st_nullifiers().is_none());
+        assert_eq!(stone_item_pod.item(), item_hash);
         let commit_main_pod = prove_st_commit_creation(
             item_def,
             created_items.clone(),
-            stone_main_pod,
+            stone_item_pod,
             &batches,
             &params,
             prover,
@@ -452,6 +703,124 @@ mod tests {
             ]),
         );
 
+        Ok(())
+    }
+    // A stone received in a transfer proves IsStone through the transferred
+    // branch of the predicate, from the IsStone statement of the original stone
+    // proven by the sender, so the recipient can craft an axe with it.
+    #[test]
+    fn test_craft_with_transferred_item() -> anyhow::Result<()> {
+        let params = Params::default();
+        let commit_preds = CommitPredicates::compile(&params);
+        let mut batches = commit_preds.defs.batches.clone();
+        // Recipes without sequential work and almost no mining
+        let recipes = RecipeRegistry::from_json(
+            r#"[
+                {
+                    "name": "Stone",
+                    "blueprint": "stone",
+                    "predicate": "IsStone",
+                    "mining_max": 4611686018427387903
+                },
+                {
+                    "name": "Axe",
+                    "blueprint": "axe",
+                    "predicate": "IsAxe",
+                    "inputs": ["stone"],
+                    "mining_max": 4611686018427387903
+                }
+            ]"#,
+        )?;
+        let stone = recipes.get("stone").unwrap();
+        let axe = recipes.get("axe").unwrap();
+        let item_preds = ItemPredicates::compile(&params, &commit_preds, &recipes);
+        batches.extend_from_slice(&item_preds.defs.batches);
+
+        let prover = &MockProver {};
+        let vd_set = &mock_vd_set();
+
+        // The sender crafts a stone.
+        let ingredients_def = MiningRecipe::new(stone.blueprint.clone(), &[])
+            .do_mining(&params, RawValue::from(0xBADC0DE), 0, stone.mining_max)?
+            .unwrap();
+        let stone_def = ItemDef::new(ingredients_def, EMPTY_VALUE);
+        let stone_main_pod = prove_item(
+            stone,
+            stone_def.clone(),
+            vec![],
+            &batches,
+            &params,
+            prover,
+            vd_set,
+        )?;
+        let stone_item_pod = ItemPod::new(stone_main_pod, &commit_preds)?;
+
+        // The sender transfers the stone, proving that the item it is
+        // transferred as is a stone too.
+        let transfer_key = RawValue::from(0xC0FFEE);
+        let key_commitment = key_commitment(transfer_key);
+        let st_original_craft = stone_item_pod.st_craft().unwrap().clone();
+        let mut planner = MainPodPlanner::new(&params, vd_set);
+        planner.builder().add_pod(stone_item_pod.into());
+        let mut item_builder =
+            ItemBuilder::new(BuildContext::new(planner.builder(), &batches), &params);
+        let st_transferred_item_def =
+            item_builder.st_transferred_item_def(stone_def.clone(), key_commitment)?;
+        let mut craft_builder =
+            CraftBuilder::new(BuildContext::new(planner.builder(), &batches), &params);
+        let st_is_stone = craft_builder.st_recipe_transferred(
+            stone,
+            st_transferred_item_def,
+            st_original_craft,
+        )?;
+        craft_builder.ctx.builder.reveal(&st_is_stone);
+        let recipe_pod = planner.prove(prover)?;
+        recipe_pod.pod.verify()?;
+
+        // The recipient claims the received stone with the transfer key.
+        let received_def = stone_def.transferred(key_commitment);
+        let mut builder = MainPodBuilder::new(&params, vd_set);
+        let st_is_stone = recipe_pod.public_statements[0].clone();
+        builder.add_pod(recipe_pod);
+        let mut item_builder = ItemBuilder::new(BuildContext::new(&mut builder, &batches), &params);
+        let st_item_key =
+            item_builder.st_transferred_item_key(received_def.clone(), transfer_key)?;
+        let st_item_def = item_builder.st_item_def(received_def.clone())?;
+        builder.reveal(&st_item_key);
+        builder.reveal(&st_item_def);
+        builder.reveal(&st_is_stone);
+        let received_main_pod = builder.prove(prover)?;
+        received_main_pod.pod.verify()?;
+        let received_item_pod = ItemPod::new(received_main_pod, &commit_preds)?;
+        let received_item = received_def.item_hash(&params)?;
+        assert_eq!(received_item_pod.item(), received_item);
+        assert!(received_item_pod.st_craft().is_some());
+
+        // The recipient crafts an axe with the received stone, consuming it
+        // with the transfer key.
+        let ingredients_def = MiningRecipe::new(axe.blueprint.clone(), &[received_item])
+            .do_mining(&params, RawValue::from(0xDEC0DE), 0, axe.mining_max)?
+            .unwrap();
+        let axe_def = ItemDef::new(ingredients_def, EMPTY_VALUE);
+        let axe_main_pod = prove_item(
+            axe,
+            axe_def.clone(),
+            vec![received_item_pod],
+            &batches,
+            &params,
+            prover,
+            vd_set,
+        )?;
+        axe_main_pod.pod.verify()?;
+        let axe_item_pod = ItemPod::new(axe_main_pod, &commit_preds)?;
+        assert_eq!(axe_item_pod.item(), axe_def.item_hash(&params)?);
+        let nullifiers =
+            set_from_hashes(&params, &HashSet::from([nullifier_of_key(transfer_key)]))?;
+        assert_eq!(
+            *axe_item_pod.st_nullifiers().unwrap().args()[0].literal()?,
+            Value::from(nullifiers)
+        );
+
         Ok(())
     }
 }
@@ -1,5 +1,6 @@
-pub mod constants;
 pub mod item;
 pub mod powpod;
 pub mod predicates;
+pub mod rangepod;
+pub mod recipes;
 mod test_util;
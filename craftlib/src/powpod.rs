@@ -36,11 +36,15 @@
 //! ```
 //! An complete example of usage can be found at the test `test_pow_pod` (bottom
 //! of this file).
+//!
+//! Long chains can be checkpointed: `PowPod::new_with_checkpoints` hands a
+//! `PowCheckpoint` to the caller after each recursive step, which can be stored
+//! and later passed to `PowPod::resume` to continue the chain where it stopped.
 
-use anyhow::{Result, anyhow};
+use anyhow::{Result, anyhow, bail};
 use itertools::Itertools;
 use plonky2::{
-    field::types::Field,
+    field::types::{Field, PrimeField64},
     hash::{
         hash_types::{HashOut, HashOutTarget},
         poseidon::PoseidonHash,
@@ -136,16 +140,70 @@ pub struct PowPod {
     pub common_hash: String,
 }
 
+/// State of the recursive chain after `count` iterations, from which the
+/// computation of the chain can be resumed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PowCheckpoint {
+    pub count: F,
+    pub input: RawValue,
+    pub output: RawValue,
+    pub proof: ProofWithPublicInputs<F, C, D>,
+}
+
 #[allow(dead_code)]
 impl PowPod {
     /// returns a PowPod for the given n_iters and input.
     pub fn new(params: &Params, vd_set: VDSet, n_iters: usize, input: RawValue) -> Result<PowPod> {
+        PowPod::new_with_checkpoints(params, vd_set, n_iters, input, |_| Ok(()))
+    }
+
+    /// returns a PowPod for the given n_iters and input, calling `on_checkpoint`
+    /// after each iteration of the recursive chain.
+    pub fn new_with_checkpoints(
+        params: &Params,
+        vd_set: VDSet,
+        n_iters: usize,
+        input: RawValue,
+        mut on_checkpoint: impl FnMut(&PowCheckpoint) -> Result<()>,
+    ) -> Result<PowPod> {
+        PowPod::prove(params, vd_set, n_iters, input, None, &mut on_checkpoint)
+    }
+
+    /// returns a PowPod for the given n_iters and input, continuing the recursive
+    /// chain from the given checkpoint, and calling `on_checkpoint` after each of
+    /// the remaining iterations.
+    pub fn resume(
+        params: &Params,
+        vd_set: VDSet,
+        n_iters: usize,
+        input: RawValue,
+        checkpoint: PowCheckpoint,
+        mut on_checkpoint: impl FnMut(&PowCheckpoint) -> Result<()>,
+    ) -> Result<PowPod> {
+        PowPod::prove(
+            params,
+            vd_set,
+            n_iters,
+            input,
+            Some(checkpoint),
+            &mut on_checkpoint,
+        )
+    }
+
+    fn prove(
+        params: &Params,
+        vd_set: VDSet,
+        n_iters: usize,
+        input: RawValue,
+        checkpoint: Option<PowCheckpoint>,
+        on_checkpoint: &mut dyn FnMut(&PowCheckpoint) -> Result<()>,
+    ) -> Result<PowPod> {
         let (last_iteration_values, proof_with_pis): (
             PowInnerCircuitInput,
             ProofWithPublicInputs<F, C, D>,
         ) = timed!(
             "PowPod::gen_pow_recursive_circuit_proof",
-            PowPod::get_pow_recursive_circuit_proof(n_iters, input)?
+            PowPod::get_pow_recursive_circuit_proof(n_iters, input, checkpoint, on_checkpoint)?
         );
 
         // generate a new PowPod from the given count, input, output
@@ -214,10 +272,13 @@ impl PowPod {
         })
     }
 
-    /// computes the PoW proof out of the RecursiveCircuit<PowInnerCircuit> circuit.
+    /// computes the PoW proof out of the RecursiveCircuit<PowInnerCircuit> circuit,
+    /// starting from the given checkpoint if any.
     fn get_pow_recursive_circuit_proof(
         n_iters: usize,
         starting_input: RawValue,
+        checkpoint: Option<PowCheckpoint>,
+        on_checkpoint: &mut dyn FnMut(&PowCheckpoint) -> Result<()>,
     ) -> Result<(PowInnerCircuitInput, ProofWithPublicInputs<F, C, D>)> {
         if n_iters < 2 {
             // this check is due the verifier_data_hash behaving differently for
@@ -231,21 +292,43 @@ impl PowPod {
             return Err(anyhow!("n_iters must be equal or greater than 2"));
         }
 
-        let mut inner_inputs = PowInnerCircuitInput {
-            prev_count: F::ZERO,
-            count: F::ONE,
-            input: starting_input,
-            midput: starting_input, // base case: midput==input
-            output: RawValue::from(pod2::middleware::hash_value(&starting_input)),
-        };
-
         let (recursive_circuit, recursive_params) = &*POW_RECURSIVE_CIRCUIT;
 
-        let (dummy_verifier_only_data, dummy_proof) =
-            dummy_recursive(recursive_params.common_data(), NUM_PUBLIC_INPUTS)?;
-        let mut recursive_proof = dummy_proof;
-        let mut recursive_verifier_only_data = dummy_verifier_only_data;
-        for i in 0..n_iters {
+        let (mut inner_inputs, mut recursive_proof, mut recursive_verifier_only_data, done_iters) =
+            match checkpoint {
+                None => {
+                    let (dummy_verifier_only_data, dummy_proof) =
+                        dummy_recursive(recursive_params.common_data(), NUM_PUBLIC_INPUTS)?;
+                    let inner_inputs = PowInnerCircuitInput {
+                        prev_count: F::ZERO,
+                        count: F::ONE,
+                        input: starting_input,
+                        midput: starting_input, // base case: midput==input
+                        output: RawValue::from(pod2::middleware::hash_value(&starting_input)),
+                    };
+                    (inner_inputs, dummy_proof, dummy_verifier_only_data, 0)
+                }
+                Some(checkpoint) => {
+                    checkpoint.check(starting_input, n_iters)?;
+                    let done_iters = checkpoint.count.to_canonical_u64() as usize;
+                    // the values of the last iteration done, from which the next
+                    // iteration continues the chain
+                    let inner_inputs = PowInnerCircuitInput {
+                        prev_count: checkpoint.count - F::ONE,
+                        count: checkpoint.count,
+                        input: checkpoint.input,
+                        midput: checkpoint.output, // overwritten by the next iteration
+                        output: checkpoint.output,
+                    };
+                    (
+                        inner_inputs,
+                        checkpoint.proof,
+                        recursive_params.verifier_data().verifier_only.clone(),
+                        done_iters,
+                    )
+                }
+            };
+        for i in done_iters..n_iters {
             if i > 0 {
                 inner_inputs.prev_count = inner_inputs.count;
                 inner_inputs.count += F::ONE;
@@ -267,11 +350,42 @@ impl PowPod {
             recursive_params
                 .verifier_data()
                 .verify(recursive_proof.clone())?;
+
+            on_checkpoint(&PowCheckpoint {
+                count: inner_inputs.count,
+                input: inner_inputs.input,
+                output: inner_inputs.output,
+                proof: recursive_proof.clone(),
+            })?;
         }
         Ok((inner_inputs, recursive_proof))
     }
 }
 
+impl PowCheckpoint {
+    /// checks that the checkpoint is a valid step of the chain starting at
+    /// `input` and of at most `n_iters` iterations.
+    fn check(&self, input: RawValue, n_iters: usize) -> Result<()> {
+        let count = self.count.to_canonical_u64() as usize;
+        if count == 0 || count > n_iters {
+            bail!("checkpoint at iteration {count} can't be resumed to {n_iters} iterations");
+        }
+        if self.input != input {
+            bail!("checkpoint doesn't belong to the chain starting at {input:#}");
+        }
+        let expected_public_inputs =
+            [&[self.count][..], &self.input.0[..], &self.output.0[..]].concat();
+        if self.proof.public_inputs[..9] != expected_public_inputs[..] {
+            bail!("checkpoint proof doesn't match its count, input and output");
+        }
+        let (_, recursive_params) = &*POW_RECURSIVE_CIRCUIT;
+        recursive_params
+            .verifier_data()
+            .verify(self.proof.clone())?;
+        Ok(())
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct Data {
     count: F,
@@ -692,7 +806,43 @@ mod tests {
     #[test]
     fn test_recursion_on_inner_circuit() -> Result<()> {
         let starting_input = RawValue::from(hash_str("starting input"));
-        let _ = PowPod::get_pow_recursive_circuit_proof(3, starting_input)?;
+        let _ = PowPod::get_pow_recursive_circuit_proof(3, starting_input, None, &mut |_| Ok(()))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_recursion_from_checkpoint() -> Result<()> {
+        let starting_input = RawValue::from(hash_str("starting input"));
+        let mut checkpoints = Vec::new();
+        let (inner_inputs, proof) =
+            PowPod::get_pow_recursive_circuit_proof(3, starting_input, None, &mut |checkpoint| {
+                checkpoints.push(checkpoint.clone());
+                Ok(())
+            })?;
+        assert_eq!(checkpoints.len(), 3);
+
+        // resume from the second iteration, as if the process stopped before the third
+        let (resumed_inner_inputs, resumed_proof) = PowPod::get_pow_recursive_circuit_proof(
+            3,
+            starting_input,
+            Some(checkpoints[1].clone()),
+            &mut |_| Ok(()),
+        )?;
+        assert_eq!(resumed_inner_inputs.count, inner_inputs.count);
+        assert_eq!(resumed_inner_inputs.output, inner_inputs.output);
+        assert_eq!(resumed_proof.public_inputs, proof.public_inputs);
+
+        // a checkpoint of a different chain can't be resumed
+        let other_input = RawValue::from(hash_str("other input"));
+        assert!(
+            PowPod::get_pow_recursive_circuit_proof(
+                3,
+                other_input,
+                Some(checkpoints[1].clone()),
+                &mut |_| Ok(()),
+            )
+            .is_err()
+        );
         Ok(())
     }
 
@@ -1,95 +1,53 @@
 use std::slice;
 
 use commitlib::predicates::CommitPredicates;
-use pod2::middleware::{CustomPredicateRef, Params};
+use pod2::middleware::Params;
 use pod2utils::PredicateDefs;
 
+use crate::{rangepod::hash_in_range_verifier_data_hash, recipes::RecipeRegistry};
+
 pub struct ItemPredicates {
     pub defs: PredicateDefs,
-
-    pub is_stone: CustomPredicateRef,
 }
 
 impl ItemPredicates {
-    pub fn compile(params: &Params, commit_preds: &CommitPredicates) -> Self {
+    pub fn compile(
+        params: &Params,
+        commit_preds: &CommitPredicates,
+        recipes: &RecipeRegistry,
+    ) -> Self {
         // maximum allowed:
         // 4 batches
         // 4 predicates per batch
         // 8 arguments per predicate, at most 5 of which are public
         // 5 statements per predicate
-        let batch_defs = [
+        let hash_in_range_vd_hash = hash_in_range_verifier_data_hash();
+        let header = format!(
             r#"
             use intro Pow(count, input, output) from 0x3493488bc23af15ac5fabe38c3cb6c4b66adb57e3898adf201ae50cc57183f65 // powpod vd hash
-        
-            // Example of a mined item with no inputs or sequential work.
-            // Stone requires working in a stone mine (blueprint="stone") and
-            // 10 leading 0s.
-            IsStone(item, private: ingredients, inputs, key, work) = AND(
-                ItemDef(item, ingredients, inputs, key, work)
-                Equal(inputs, {})
-                DictContains(ingredients, "blueprint", "stone")
-                Pow(3, ingredients, work)
-            )
-        
-            // Example of a mined item which is more common but takes more work to
-            // extract.
-            IsWood(item, private: ingredients, inputs, key, work) = AND(
-                ItemDef(item, ingredients, inputs, key, work)
-                Equal(inputs, {})
-                DictContains(ingredients, "blueprint", "wood")
-                Equal(work, {})
-                // TODO input POD: SequentialWork(ingredients, work, 5)
-                // TODO input POD: HashInRange(0, 1<<5, ingredients)
-            )
-            "#,
-            r#"
-            AxeInputs(inputs, private: s1, wood, stone) = AND(
-                // 2 ingredients
-                SetInsert(s1, {}, wood)
-                SetInsert(inputs, s1, stone)
-        
-                // prove the ingredients are correct.
-                IsWood(wood)
-                IsStone(stone)
-            )
-        
-            // Combining Stone and Wood to get Axe is easy (no sequential work).
-            // TODO: Require a smelter as a tool
-            IsAxe(item, private: ingredients, inputs, key, work) = AND(
-                ItemDef(item, ingredients, inputs, key, work)
-                DictContains(ingredients, "blueprint", "axe")
-                Equal(work, {})
-        
-                AxeInputs(inputs)
-            )
-        
-            // Wooden Axe:
-            WoodenAxeInputs(inputs, private: s1, wood1, wood2) = AND(
-                // 2 ingredients
-                SetInsert(s1, {}, wood1)
-                SetInsert(inputs, s1, wood2)
-        
-                // prove the ingredients are correct.
-                IsWood(wood1)
-                IsWood(wood2)
-            )
-
-            // Combine Wood and Wod to get WoodenAxe.
-            IsWoodenAxe(item, private: ingredients, inputs, key, work) = AND(
-                ItemDef(item, ingredients, inputs, key, work)
-                DictContains(ingredients, "blueprint", "wooden-axe")
-                Equal(work, {})
-        
-                WoodenAxeInputs(inputs)
-            )
-            "#,
-        ];
+            use intro HashInRange(max, input) from {hash_in_range_vd_hash:#} // rangepod vd hash
+            "#
+        );
+        // The predicates of the recipes are declared in order, filling each
+        // batch before starting the next one without splitting the groups of
+        // predicates which refer to each other.
+        let mut batches: Vec<Vec<String>> = Vec::new();
+        for group in recipes.podlang() {
+            match batches.last_mut() {
+                Some(batch) if batch.len() + group.len() <= params.max_custom_batch_size => {
+                    batch.extend(group)
+                }
+                _ => batches.push(group),
+            }
+        }
+        let batch_defs: Vec<String> = batches
+            .iter()
+            .map(|predicates| format!("{header}\n{}", predicates.join("\n\n")))
+            .collect();
+        let batch_defs: Vec<&str> = batch_defs.iter().map(String::as_str).collect();
         let defs = PredicateDefs::new(params, &batch_defs, slice::from_ref(&commit_preds.defs));
 
-        ItemPredicates {
-            is_stone: defs.predicate_ref_by_name("IsStone").unwrap(),
-            defs,
-        }
+        ItemPredicates { defs }
     }
 }
 
@@ -107,8 +65,8 @@ mod tests {
 
     use super::*;
     use crate::{
-        constants::STONE_BLUEPRINT,
         powpod::PowPod,
+        rangepod::HashInRangePod,
         test_util::test::{check_matched_wildcards, mock_vd_set},
     };
 
@@ -116,17 +74,54 @@ mod tests {
     fn test_compile_custom_predicates() {
         let params = Params::default();
         let commit_preds = CommitPredicates::compile(&params);
-        assert!(commit_preds.defs.batches.len() == 2);
+        assert!(commit_preds.defs.batches.len() == 4);
 
-        let item_preds = ItemPredicates::compile(&params, &commit_preds);
-        assert!(item_preds.defs.batches.len() == 2);
+        let item_preds =
+            ItemPredicates::compile(&params, &commit_preds, &RecipeRegistry::default());
+        assert!(item_preds.defs.batches.len() == 4);
+    }
+
+    #[test]
+    fn test_compile_chained_inputs() -> anyhow::Result<()> {
+        let params = Params::default();
+        let commit_preds = CommitPredicates::compile(&params);
+        let recipes = RecipeRegistry::from_json(
+            r#"[
+                {"name": "Wood", "blueprint": "wood", "predicate": "IsWood", "mining_max": 1024},
+                {"name": "Stone", "blueprint": "stone", "predicate": "IsStone", "mining_max": 1024},
+                {
+                    "name": "House",
+                    "blueprint": "house",
+                    "predicate": "IsHouse",
+                    "inputs": ["wood", "wood", "stone", "wood", "stone"],
+                    "mining_max": 1024
+                }
+            ]"#,
+        )?;
+        let item_preds = ItemPredicates::compile(&params, &commit_preds, &recipes);
+        for name in [
+            "HouseInputs1",
+            "HouseInputs2",
+            "HouseInputs",
+            "IsHouse",
+            "IsHouseCrafted",
+            "IsHouseTransferred",
+        ] {
+            assert!(
+                item_preds.defs.predicate_ref_by_name(name).is_some(),
+                "{name}"
+            );
+        }
+        Ok(())
     }
 
     #[test]
     fn test_build_pod_no_inputs() -> anyhow::Result<()> {
         let params = Params::default();
         let commit_preds = CommitPredicates::compile(&params);
-        let item_preds = ItemPredicates::compile(&params, &commit_preds);
+        let recipes = RecipeRegistry::default();
+        let item_preds = ItemPredicates::compile(&params, &commit_preds, &recipes);
+        let stone = recipes.get("stone").unwrap();
 
         let mut builder = MainPodBuilder::new(&Default::default(), &mock_vd_set());
 
@@ -139,7 +134,10 @@ mod tests {
             inputs: HashSet::new(),
             key: RawValue::from(key),
             app_layer: HashMap::from([
-                ("blueprint".to_string(), Value::from(STONE_BLUEPRINT)),
+                (
+                    "blueprint".to_string(),
+                    Value::from(stone.blueprint.as_str()),
+                ),
                 ("seed".to_string(), Value::from(seed)),
             ]),
         };
@@ -150,7 +148,7 @@ mod tests {
         let pow_pod = PowPod::new(
             &params,
             vd_set.clone(),
-            3,
+            stone.pow_iters,
             RawValue::from(ingredients_def.dict(&params)?.commitment()),
         )?;
         let main_pow_pod = MainPod {
@@ -159,8 +157,21 @@ mod tests {
             params: params.clone(),
         };
         let work: RawValue = pow_pod.output;
+        let range_pod = HashInRangePod::new(
+            &params,
+            vd_set.clone(),
+            stone.mining_max,
+            RawValue::from(ingredients_dict.commitment()),
+        )?;
+        let main_range_pod = MainPod {
+            pod: Box::new(range_pod.clone()),
+            public_statements: range_pod.pub_statements(),
+            params: params.clone(),
+        };
         let st_pow = main_pow_pod.public_statements[0].clone();
         builder.add_pod(main_pow_pod);
+        let st_hash_in_range = main_range_pod.public_statements[0].clone();
+        builder.add_pod(main_range_pod);
         let item_def = ItemDef {
             ingredients: ingredients_def.clone(),
             work,
@@ -199,9 +210,17 @@ mod tests {
         ))?;
 
         // Build ItemKey(item, key)
+        let st_not_contains_key_commitment = builder.priv_op(Operation::dict_not_contains(
+            ingredients_dict.clone(),
+            "key_commitment",
+        ))?;
+        let st_crafted_item_key = builder.priv_op(Operation::custom(
+            commit_preds.crafted_item_key.clone(),
+            [st_item_def.clone(), st_not_contains_key_commitment],
+        ))?;
         let _st_itemkey = builder.pub_op(Operation::custom(
             commit_preds.item_key.clone(),
-            [st_item_def.clone()],
+            [st_crafted_item_key, Statement::None],
         ))?;
 
         // Build SubsetOf(inputs, created_items)
@@ -240,17 +259,28 @@ mod tests {
         let st_contains_blueprint = builder.priv_op(Operation::dict_contains(
             ingredients_dict.clone(),
             "blueprint",
-            Value::from(STONE_BLUEPRINT),
+            Value::from(stone.blueprint.as_str()),
         ))?;
-        let _st_is_stone = builder.pub_op(Operation::custom(
-            item_preds.is_stone.clone(),
+        let st_is_stone_crafted = builder.priv_op(Operation::custom(
+            item_preds
+                .defs
+                .predicate_ref_by_name(&stone.crafted_predicate())
+                .unwrap(),
             [
                 st_item_def,
                 st_inputs_eq_empty,
                 st_contains_blueprint,
+                st_hash_in_range,
                 st_pow,
             ],
         ))?;
+        let _st_is_stone = builder.pub_op(Operation::custom(
+            item_preds
+                .defs
+                .predicate_ref_by_name(&stone.predicate)
+                .unwrap(),
+            [st_is_stone_crafted, Statement::None],
+        ))?;
 
         // Prove MainPOD
         let main_pod = builder.prove(&MockProver {})?;
@@ -0,0 +1,450 @@
+//! HashInRangePod: Introduction Pod proving that the first field element of a
+//! hash is at most a given maximum, which is how the mining difficulty of an
+//! item is defined (see `MiningRecipe::do_mining`).
+//!
+//! Exposes the statement `HashInRange(max, input)`, where `max` is an Int and
+//! `input` is the hash (typically the ingredients dict of an item).
+//!
+//! The comparison is done on field elements, so both `max` and the first
+//! element of `input` are constrained to be smaller than 2^62.  The difference
+//! `max - input[0]` is then in (-2^62, 2^62), and it is in range iff it's not
+//! negative, since a negative difference wraps around to a field element bigger
+//! than 2^62.
+//!
+//! Usage:
+//! ```rust
+//!   use pod2::{backends::plonky2::basetypes::DEFAULT_VD_SET, middleware::{Params, RawValue, hash_str}};
+//!   use craftlib::rangepod::HashInRangePod;
+//!
+//!   let params = Params::default();
+//!   let vd_set = &*DEFAULT_VD_SET;
+//!   let input = RawValue::from(hash_str("starting input"));
+//!   let max = u64::MAX >> 2;
+//!   let range_pod = HashInRangePod::new(&params, vd_set.clone(), max, input).unwrap();
+//! ```
+
+use anyhow::{Result, anyhow};
+use itertools::Itertools;
+use plonky2::{
+    field::types::{Field, PrimeField64},
+    hash::hash_types::{HashOut, HashOutTarget},
+    iop::witness::{PartialWitness, WitnessWrite},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitData, VerifierOnlyCircuitData},
+        proof::ProofWithPublicInputs,
+    },
+};
+use pod2::{
+    backends::plonky2::{
+        Error, Result as BResult,
+        circuits::{
+            common::{
+                CircuitBuilderPod, PredicateTarget, StatementArgTarget, StatementTarget,
+                ValueTarget,
+            },
+            mainpod::calculate_statements_hash_circuit,
+        },
+        deserialize_proof, mainpod,
+        mainpod::calculate_statements_hash,
+        recursion::circuit::hash_verifier_data,
+        serialize_proof,
+    },
+    measure_gates_begin, measure_gates_end, middleware,
+    middleware::{
+        C, D, EMPTY_HASH, F, Hash, IntroPredicateRef, Params, Pod, Proof, RawValue, ToFields,
+        VDSet, Value,
+    },
+    timed,
+};
+use serde::{Deserialize, Serialize};
+
+const HASH_IN_RANGE_POD_TYPE: (usize, &str) = (2002, "HashInRange");
+/// Number of bits of the values compared in the circuit
+pub(crate) const RANGE_BITS: usize = 62;
+
+static STANDARD_HASH_IN_RANGE_POD_DATA: std::sync::LazyLock<(
+    HashInRangePodTarget,
+    CircuitData<F, C, D>,
+)> = std::sync::LazyLock::new(|| build().expect("successful build"));
+fn build() -> Result<(HashInRangePodTarget, CircuitData<F, C, D>)> {
+    let params = Params::default();
+
+    // use pod2's recursion config as config for the introduction pod; which if
+    // the zk feature enabled, it will have the zk property enabled
+    let rec_circuit_data =
+        &*pod2::backends::plonky2::cache_get_standard_rec_main_pod_common_circuit_data();
+
+    let common_data = rec_circuit_data.0.clone();
+    let config = common_data.config.clone();
+
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let target = HashInRangePodTarget::add_targets(&mut builder, &params)?;
+    pod2::backends::plonky2::recursion::pad_circuit(&mut builder, &common_data);
+
+    let data = timed!("HashInRangePod build", builder.build::<C>());
+    assert_eq!(common_data, data.common);
+    Ok((target, data))
+}
+
+/// Hash of the verifier data of the HashInRangePod, to import it in predicates
+/// with `use intro HashInRange(max, input) from 0x...`.
+pub fn hash_in_range_verifier_data_hash() -> Hash {
+    let (_, circuit_data) = &*STANDARD_HASH_IN_RANGE_POD_DATA;
+    Hash(hash_verifier_data(&circuit_data.verifier_only).elements)
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HashInRangePod {
+    pub params: Params,
+    pub max: u64,
+    pub input: RawValue, // input[0] <= max
+
+    pub vd_set: VDSet,
+    pub statements_hash: Hash,
+    pub proof: Proof,
+
+    pub common_hash: String,
+}
+
+impl HashInRangePod {
+    /// returns a HashInRangePod for the given max and input.
+    pub fn new(params: &Params, vd_set: VDSet, max: u64, input: RawValue) -> Result<Self> {
+        if max >= 1 << RANGE_BITS {
+            return Err(anyhow!("max must be smaller than 2^{RANGE_BITS}"));
+        }
+        if input.0[0].to_canonical_u64() > max {
+            return Err(anyhow!("input {input:#} is not in range"));
+        }
+
+        let (target, circuit_data) = &*STANDARD_HASH_IN_RANGE_POD_DATA;
+        let statements = pub_self_statements(max, input)
+            .into_iter()
+            .map(mainpod::Statement::from)
+            .collect_vec();
+        let statements_hash: Hash = calculate_statements_hash(&statements, params);
+
+        let mut pw = PartialWitness::<F>::new();
+        target.set_targets(
+            &mut pw,
+            &HashInRangePodVerifyInput {
+                vd_root: vd_set.root(),
+                statements_hash,
+                max,
+                input,
+            },
+        )?;
+        let proof_with_pis = timed!("prove HashInRangePod", circuit_data.prove(pw)?);
+        // sanity check
+        circuit_data
+            .verifier_data()
+            .verify(proof_with_pis.clone())?;
+
+        let common_hash: String =
+            pod2::backends::plonky2::mainpod::cache_get_rec_main_pod_common_hash(params).clone();
+
+        Ok(Self {
+            params: params.clone(),
+            max,
+            input,
+            vd_set,
+            statements_hash,
+            proof: proof_with_pis.proof,
+            common_hash,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Data {
+    max: u64,
+    input: RawValue,
+    proof: String,
+    common_hash: String,
+}
+
+impl Pod for HashInRangePod {
+    fn params(&self) -> &Params {
+        &self.params
+    }
+    fn verify(&self) -> BResult<()> {
+        let statements = pub_self_statements(self.max, self.input)
+            .into_iter()
+            .map(mainpod::Statement::from)
+            .collect_vec();
+        let statements_hash: Hash = calculate_statements_hash(&statements, &self.params);
+        if statements_hash != self.statements_hash {
+            return Err(Error::statements_hash_not_equal(
+                self.statements_hash,
+                statements_hash,
+            ));
+        }
+
+        let (_, circuit_data) = &*STANDARD_HASH_IN_RANGE_POD_DATA;
+
+        let public_inputs = statements_hash
+            .to_fields(&self.params)
+            .iter()
+            .chain(self.vd_set().root().0.iter())
+            .cloned()
+            .collect_vec();
+
+        circuit_data
+            .verify(ProofWithPublicInputs {
+                proof: self.proof.clone(),
+                public_inputs,
+            })
+            .map_err(|e| Error::custom(format!("HashInRangePod proof verification failure: {e:?}")))
+    }
+
+    fn statements_hash(&self) -> Hash {
+        self.statements_hash
+    }
+
+    fn pod_type(&self) -> (usize, &'static str) {
+        HASH_IN_RANGE_POD_TYPE
+    }
+
+    fn pub_self_statements(&self) -> Vec<middleware::Statement> {
+        pub_self_statements(self.max, self.input)
+    }
+
+    fn serialize_data(&self) -> serde_json::Value {
+        serde_json::to_value(Data {
+            max: self.max,
+            input: self.input,
+            proof: serialize_proof(&self.proof),
+            common_hash: self.common_hash.clone(),
+        })
+        .expect("serialization to json")
+    }
+    fn deserialize_data(
+        params: Params,
+        data: serde_json::Value,
+        vd_set: VDSet,
+        statements_hash: Hash,
+    ) -> BResult<Self> {
+        let data: Data = serde_json::from_value(data)?;
+        let common =
+            &*pod2::backends::plonky2::cache_get_standard_rec_main_pod_common_circuit_data();
+        let proof = deserialize_proof(common, &data.proof)?;
+        Ok(Self {
+            params,
+            max: data.max,
+            input: data.input,
+            vd_set,
+            statements_hash,
+            proof,
+            common_hash: data.common_hash,
+        })
+    }
+
+    fn verifier_data(&self) -> VerifierOnlyCircuitData<C, D> {
+        STANDARD_HASH_IN_RANGE_POD_DATA
+            .1
+            .verifier_data()
+            .verifier_only
+            .clone()
+    }
+
+    fn common_hash(&self) -> String {
+        self.common_hash.clone()
+    }
+    fn proof(&self) -> Proof {
+        self.proof.clone()
+    }
+    fn vd_set(&self) -> &VDSet {
+        &self.vd_set
+    }
+}
+
+fn pub_self_statements(max: u64, input: RawValue) -> Vec<middleware::Statement> {
+    vec![middleware::Statement::Intro(
+        IntroPredicateRef {
+            name: HASH_IN_RANGE_POD_TYPE.1.to_string(),
+            args_len: 2,
+            verifier_data_hash: EMPTY_HASH,
+        },
+        vec![Value::from(max as i64), input.into()],
+    )]
+}
+fn pub_self_statements_target(
+    builder: &mut CircuitBuilder<F, D>,
+    params: &Params,
+    max: &ValueTarget,
+    input: &ValueTarget,
+) -> Vec<StatementTarget> {
+    let st_arg_0 = StatementArgTarget::literal(builder, max);
+    let st_arg_1 = StatementArgTarget::literal(builder, input);
+    let args = [st_arg_0, st_arg_1]
+        .into_iter()
+        .chain(core::iter::repeat_with(|| {
+            StatementArgTarget::none(builder)
+        }))
+        .take(params.max_statement_args)
+        .collect();
+
+    let verifier_data_hash = builder.constant_hash(HashOut {
+        elements: EMPTY_HASH.0,
+    });
+    let predicate = PredicateTarget::new_intro(builder, verifier_data_hash);
+    vec![StatementTarget { predicate, args }]
+}
+
+#[derive(Clone, Debug)]
+struct HashInRangePodTarget {
+    vd_root: HashOutTarget,
+    statements_hash: HashOutTarget,
+    max: ValueTarget,   // Int encoding: [low 32 bits, high 32 bits, 0, 0]
+    input: ValueTarget, // hash
+}
+struct HashInRangePodVerifyInput {
+    vd_root: Hash,
+    statements_hash: Hash,
+    max: u64,
+    input: RawValue,
+}
+impl HashInRangePodTarget {
+    fn add_targets(builder: &mut CircuitBuilder<F, D>, params: &Params) -> Result<Self> {
+        let measure = measure_gates_begin!(builder, "HashInRangePodTarget");
+
+        let max = builder.add_virtual_value();
+        let input = builder.add_virtual_value();
+
+        // max = low + high * 2^32 < 2^62, with the Int encoding of a non-negative value
+        let zero = builder.zero();
+        builder.range_check(max.elements[0], 32);
+        builder.range_check(max.elements[1], RANGE_BITS - 32);
+        builder.connect(max.elements[2], zero);
+        builder.connect(max.elements[3], zero);
+        let max_value = builder.mul_const_add(
+            F::from_canonical_u64(1 << 32),
+            max.elements[1],
+            max.elements[0],
+        );
+
+        // input[0] <= max
+        builder.range_check(input.elements[0], RANGE_BITS);
+        let diff = builder.sub(max_value, input.elements[0]);
+        builder.range_check(diff, RANGE_BITS);
+
+        // calculate statements_hash
+        let statements = pub_self_statements_target(builder, params, &max, &input);
+        let statements_hash = calculate_statements_hash_circuit(params, builder, &statements);
+
+        // register the public inputs
+        let vd_root = builder.add_virtual_hash();
+        builder.register_public_inputs(&statements_hash.elements);
+        builder.register_public_inputs(&vd_root.elements);
+
+        measure_gates_end!(builder, measure);
+        Ok(HashInRangePodTarget {
+            vd_root,
+            statements_hash,
+            max,
+            input,
+        })
+    }
+
+    fn set_targets(
+        &self,
+        pw: &mut PartialWitness<F>,
+        input: &HashInRangePodVerifyInput,
+    ) -> Result<()> {
+        pw.set_target_arr(&self.max.elements, &Value::from(input.max as i64).raw().0)?;
+        pw.set_target_arr(&self.input.elements, &input.input.0)?;
+        pw.set_hash_target(
+            self.statements_hash,
+            HashOut::from_vec(input.statements_hash.0.to_vec()),
+        )?;
+        pw.set_target_arr(&self.vd_root.elements, &input.vd_root.0)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pod2::{backends::plonky2::basetypes::DEFAULT_VD_SET, frontend, middleware::hash_str};
+
+    use super::*;
+
+    #[test]
+    fn test_hash_in_range_pod() -> Result<()> {
+        let params = Params::default();
+        let vd_set = &*DEFAULT_VD_SET;
+
+        // Find an input in range
+        let max = 1 << 60;
+        let input = (0..)
+            .map(|i| RawValue::from(hash_str(&format!("input {i}"))))
+            .find(|input| input.0[0].to_canonical_u64() <= max)
+            .unwrap();
+
+        let range_pod = HashInRangePod::new(&params, vd_set.clone(), max, input)?;
+        range_pod.verify()?;
+
+        println!(
+            "range_pod.verifier_data_hash(): {:#}",
+            range_pod.verifier_data_hash()
+        );
+        assert_eq!(
+            range_pod.verifier_data_hash(),
+            hash_in_range_verifier_data_hash()
+        );
+
+        // wrap the range_pod in a 'MainPod'
+        let main_range_pod = frontend::MainPod {
+            pod: Box::new(range_pod.clone()),
+            public_statements: range_pod.pub_statements(),
+            params: params.clone(),
+        };
+        let mut main_pod_builder = frontend::MainPodBuilder::new(&params, vd_set);
+        main_pod_builder.add_pod(main_range_pod.clone());
+        main_pod_builder.reveal(&main_range_pod.public_statements[0]);
+
+        let prover = pod2::backends::plonky2::mock::mainpod::MockProver {};
+        let pod = main_pod_builder.prove(&prover)?;
+        pod.pod.verify()?;
+
+        let st_range = pod.pod.pub_statements()[0].clone();
+        assert_eq!(st_range.args()[0].literal()?, Value::from(max as i64));
+        assert_eq!(st_range.args()[1].literal()?, Value::from(input));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_out_of_range() {
+        let params = Params::default();
+        let vd_set = &*DEFAULT_VD_SET;
+
+        let max = 1 << 60;
+        let input = (0..)
+            .map(|i| RawValue::from(hash_str(&format!("input {i}"))))
+            .find(|input| input.0[0].to_canonical_u64() > max)
+            .unwrap();
+        assert!(HashInRangePod::new(&params, vd_set.clone(), max, input).is_err());
+
+        // The circuit rejects it too
+        let (target, circuit_data) = &*STANDARD_HASH_IN_RANGE_POD_DATA;
+        let statements = pub_self_statements(max, input)
+            .into_iter()
+            .map(mainpod::Statement::from)
+            .collect_vec();
+        let mut pw = PartialWitness::<F>::new();
+        target
+            .set_targets(
+                &mut pw,
+                &HashInRangePodVerifyInput {
+                    vd_root: vd_set.root(),
+                    statements_hash: calculate_statements_hash(&statements, &params),
+                    max,
+                    input,
+                },
+            )
+            .unwrap();
+        let proof = circuit_data.prove(pw);
+        assert!(proof.and_then(|proof| circuit_data.verify(proof)).is_err());
+    }
+}
@@ -0,0 +1,408 @@
+//! Registry of the recipes that can be crafted, loaded from a declarative JSON
+//! file (see `recipes.json` at the root of this crate for the default one).
+//!
+//! Each recipe defines the blueprint of the item, the blueprints of its inputs,
+//! the sequential work and the mining difficulty.  The custom predicate of the
+//! recipe is generated from these, so the PODLang and the crafting code always
+//! agree.  A recipe with inputs generates a chain of inputs predicates followed
+//! by the predicate of the item:
+//!
+//! ```text
+//! AxeInputs(inputs, private: s1, input1, input2) = AND(
+//!     SetInsert(s1, {}, input1)
+//!     SetInsert(inputs, s1, input2)
+//!     IsWood(input1)
+//!     IsStone(input2)
+//! )
+//!
+//! IsAxeCrafted(item, private: ingredients, inputs, key, work) = AND(
+//!     ItemDef(item, ingredients, inputs, key, work)
+//!     AxeInputs(inputs)
+//!     DictContains(ingredients, "blueprint", "axe")
+//!     HashInRange(9007199254740992, ingredients)
+//!     Equal(work, {})
+//! )
+//! ```
+//!
+//! Each inputs predicate proves up to [`INPUTS_PER_PREDICATE`] inputs on top of
+//! the set proved by the previous one, so that any number of inputs fits in the
+//! statement limit of a predicate:
+//!
+//! ```text
+//! HouseInputs1(inputs, private: s1, input1, input2) = AND(
+//!     SetInsert(s1, {}, input1)
+//!     SetInsert(inputs, s1, input2)
+//!     IsWood(input1)
+//!     IsWood(input2)
+//! )
+//!
+//! HouseInputs(inputs, private: rest, input3) = AND(
+//!     HouseInputs1(rest)
+//!     SetInsert(inputs, rest, input3)
+//!     IsStone(input3)
+//! )
+//! ```
+//!
+//! A recipe without inputs (a mined item) only generates the predicate of the
+//! item, with `Equal(inputs, {})` instead of the inputs predicate.
+//!
+//! The predicate of the item holds both for the items crafted with the recipe
+//! and for the items they are transferred as, so that received items can be
+//! used as inputs too:
+//!
+//! ```text
+//! IsAxe(item) = OR(
+//!     IsAxeCrafted(item)
+//!     IsAxeTransferred(item)
+//! )
+//!
+//! IsAxeTransferred(item, private: original, key_commitment) = AND(
+//!     TransferredItemDef(item, original, key_commitment)
+//!     IsAxe(original)
+//! )
+//! ```
+//!
+//! As `IsAxe` and `IsAxeTransferred` refer to each other, the three predicates
+//! are declared in the same batch.
+
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::rangepod::RANGE_BITS;
+
+/// Default registry, embedded in the binary
+pub const DEFAULT_RECIPES: &str = include_str!("../recipes.json");
+/// Number of inputs proved by each predicate of the inputs chain, as each input
+/// takes 2 of the 5 statements of a predicate and the previous link takes one
+pub const INPUTS_PER_PREDICATE: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecipeDef {
+    // Name of the item, used for display
+    pub name: String,
+    // Value of the "blueprint" ingredient, which identifies the recipe
+    pub blueprint: String,
+    // Name of the custom predicate proving that an item follows the recipe
+    pub predicate: String,
+    #[serde(default)]
+    pub description: String,
+    // Blueprints of the inputs, in order
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    // Maximum of the ingredients hash, which sets the mining difficulty
+    pub mining_max: u64,
+    // Number of PowPod iterations of the sequential work, 0 if the item requires none
+    #[serde(default)]
+    pub pow_iters: usize,
+}
+
+impl RecipeDef {
+    /// Name of the custom predicate proving all the inputs of the recipe.
+    pub fn inputs_predicate(&self) -> String {
+        format!("{}Inputs", self.name)
+    }
+
+    /// Names of the chain of custom predicates proving the inputs of the
+    /// recipe, each one extending the set of the previous one with the next
+    /// [`INPUTS_PER_PREDICATE`] inputs.  The last one is `inputs_predicate`.
+    pub fn inputs_predicates(&self) -> Vec<String> {
+        let n = self.inputs.len().div_ceil(INPUTS_PER_PREDICATE);
+        (1..=n)
+            .map(|i| {
+                if i == n {
+                    self.inputs_predicate()
+                } else {
+                    format!("{}{i}", self.inputs_predicate())
+                }
+            })
+            .collect()
+    }
+
+    /// Name of the custom predicate proving that an item was crafted with the
+    /// recipe.
+    pub fn crafted_predicate(&self) -> String {
+        format!("{}Crafted", self.predicate)
+    }
+
+    /// Name of the custom predicate proving that an item is the transfer of an
+    /// item following the recipe.
+    pub fn transferred_predicate(&self) -> String {
+        format!("{}Transferred", self.predicate)
+    }
+
+    /// Statement of the recipe's predicate constraining the `work` of the item.
+    pub fn work_statement(&self) -> String {
+        if self.pow_iters == 0 {
+            "Equal(work, {})".to_string()
+        } else {
+            format!("Pow({}, ingredients, work)", self.pow_iters)
+        }
+    }
+
+    /// PODLang definitions of the custom predicates of the recipe, in the order
+    /// in which they must be declared, grouped by the definitions which must be
+    /// declared in the same batch.
+    pub fn podlang(&self, registry: &RecipeRegistry) -> Vec<Vec<String>> {
+        let mut defs = Vec::new();
+        let links = self.inputs_predicates();
+        let groups = self.inputs.chunks(INPUTS_PER_PREDICATE);
+        for (i, (link, group)) in links.iter().zip(groups).enumerate() {
+            let first = i * INPUTS_PER_PREDICATE + 1;
+            let items: Vec<String> = (first..first + group.len())
+                .map(|j| format!("input{j}"))
+                .collect();
+            let mut private = Vec::new();
+            let mut statements = Vec::new();
+            // Set of the inputs proved by the previous link
+            let mut set = if i == 0 {
+                "{}".to_string()
+            } else {
+                private.push("rest".to_string());
+                statements.push(format!("{}(rest)", links[i - 1]));
+                "rest".to_string()
+            };
+            for (j, item) in items.iter().enumerate() {
+                let new_set = if j == items.len() - 1 {
+                    "inputs".to_string()
+                } else {
+                    let new_set = format!("s{}", first + j);
+                    private.push(new_set.clone());
+                    new_set
+                };
+                statements.push(format!("SetInsert({new_set}, {set}, {item})"));
+                set = new_set;
+            }
+            for (item, blueprint) in items.iter().zip(group) {
+                let input = registry.get(blueprint).expect("validated input");
+                statements.push(format!("{}({item})", input.predicate));
+            }
+            private.extend(items);
+            defs.push(vec![format!(
+                "{link}(inputs, private: {}) = AND(\n    {}\n)",
+                private.join(", "),
+                statements.join("\n    "),
+            )]);
+        }
+        let st_inputs = if self.inputs.is_empty() {
+            "Equal(inputs, {})".to_string()
+        } else {
+            format!("{}(inputs)", self.inputs_predicate())
+        };
+        defs.push(vec![
+            format!(
+                "{}(item) = OR(\n    {}(item)\n    {}(item)\n)",
+                self.predicate,
+                self.crafted_predicate(),
+                self.transferred_predicate(),
+            ),
+            format!(
+                r#"{}(item, private: ingredients, inputs, key, work) = AND(
+    ItemDef(item, ingredients, inputs, key, work)
+    {st_inputs}
+    DictContains(ingredients, "blueprint", "{}")
+    HashInRange({}, ingredients)
+    {}
+)"#,
+                self.crafted_predicate(),
+                self.blueprint,
+                self.mining_max,
+                self.work_statement(),
+            ),
+            format!(
+                r#"{}(item, private: original, key_commitment) = AND(
+    TransferredItemDef(item, original, key_commitment)
+    {}(original)
+)"#,
+                self.transferred_predicate(),
+                self.predicate,
+            ),
+        ]);
+        defs
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipeRegistry {
+    recipes: Vec<RecipeDef>,
+}
+
+impl RecipeRegistry {
+    /// Builds a registry from recipes listed after the recipes of their inputs.
+    pub fn new(recipes: Vec<RecipeDef>) -> Result<Self> {
+        let mut names = HashSet::new();
+        let mut blueprints = HashSet::new();
+        for recipe in &recipes {
+            for name in [&recipe.name, &recipe.predicate] {
+                if !name.starts_with(|c: char| c.is_ascii_alphabetic())
+                    || !name.chars().all(|c| c.is_ascii_alphanumeric())
+                {
+                    bail!("Recipe name or predicate {name:?} is not an identifier");
+                }
+            }
+            if recipe.blueprint.is_empty() || recipe.blueprint.contains(['"', '\\']) {
+                bail!("Invalid blueprint {:?}", recipe.blueprint);
+            }
+            let predicates = [
+                recipe.predicate.clone(),
+                recipe.crafted_predicate(),
+                recipe.transferred_predicate(),
+            ];
+            for name in [predicates.to_vec(), recipe.inputs_predicates()].concat() {
+                if !names.insert(name.clone()) {
+                    bail!("Duplicated predicate {name}");
+                }
+            }
+            if let Some(input) = recipe.inputs.iter().find(|i| !blueprints.contains(*i)) {
+                bail!(
+                    "Input {input} of recipe {} is not defined before it",
+                    recipe.blueprint
+                );
+            }
+            if recipe.mining_max >= 1 << RANGE_BITS {
+                bail!(
+                    "Mining max of recipe {} must be smaller than 2^{RANGE_BITS}",
+                    recipe.blueprint
+                );
+            }
+            // PowPod requires at least 2 iterations
+            if recipe.pow_iters == 1 {
+                bail!(
+                    "Recipe {} requires 0 or at least 2 PowPod iterations",
+                    recipe.blueprint
+                );
+            }
+            if !blueprints.insert(recipe.blueprint.clone()) {
+                bail!("Duplicated blueprint {}", recipe.blueprint);
+            }
+        }
+        Ok(Self { recipes })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Self::new(serde_json::from_str(json)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    pub fn recipes(&self) -> &[RecipeDef] {
+        &self.recipes
+    }
+
+    pub fn get(&self, blueprint: &str) -> Option<&RecipeDef> {
+        self.recipes
+            .iter()
+            .find(|recipe| recipe.blueprint == blueprint)
+    }
+
+    /// PODLang definitions of the custom predicates of all the recipes, in
+    /// declaration order, grouped as in [`RecipeDef::podlang`].
+    pub fn podlang(&self) -> Vec<Vec<String>> {
+        self.recipes
+            .iter()
+            .flat_map(|recipe| recipe.podlang(self))
+            .collect()
+    }
+}
+
+impl Default for RecipeRegistry {
+    fn default() -> Self {
+        Self::from_json(DEFAULT_RECIPES).expect("valid default recipes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipe(blueprint: &str, inputs: &[&str]) -> RecipeDef {
+        let mut name = blueprint.to_string();
+        name[..1].make_ascii_uppercase();
+        RecipeDef {
+            predicate: format!("Is{name}"),
+            name,
+            blueprint: blueprint.to_string(),
+            description: String::new(),
+            inputs: inputs.iter().map(|input| input.to_string()).collect(),
+            mining_max: 1 << 60,
+            pow_iters: 0,
+        }
+    }
+
+    #[test]
+    fn test_default_recipes() {
+        let registry = RecipeRegistry::default();
+        assert_eq!(registry.recipes().len(), 4);
+        let axe = registry.get("axe").unwrap();
+        assert_eq!(axe.inputs, ["wood", "stone"]);
+        let podlang = axe.podlang(&registry);
+        assert_eq!(podlang.len(), 2);
+        assert!(podlang[0][0].starts_with("AxeInputs(inputs, private: s1, input1, input2)"));
+        assert!(podlang[0][0].contains("IsWood(input1)"));
+        assert!(podlang[0][0].contains("IsStone(input2)"));
+        assert_eq!(podlang[1].len(), 3);
+        assert!(podlang[1][0].starts_with("IsAxe(item) = OR("));
+        assert!(podlang[1][1].starts_with("IsAxeCrafted(item, private:"));
+        assert!(podlang[1][1].contains("AxeInputs(inputs)"));
+        assert!(podlang[1][2].starts_with("IsAxeTransferred(item, private:"));
+        assert!(podlang[1][2].contains("IsAxe(original)"));
+        let stone = registry.get("stone").unwrap();
+        assert!(stone.podlang(&registry)[0][1].contains("Pow(3, ingredients, work)"));
+    }
+
+    #[test]
+    fn test_chained_inputs() -> Result<()> {
+        let registry = RecipeRegistry::new(vec![
+            recipe("wood", &[]),
+            recipe("stone", &[]),
+            recipe("house", &["wood", "wood", "stone", "wood", "stone"]),
+        ])?;
+        let house = registry.get("house").unwrap();
+        assert_eq!(
+            house.inputs_predicates(),
+            ["HouseInputs1", "HouseInputs2", "HouseInputs"]
+        );
+        let podlang: Vec<String> = house.podlang(&registry).concat();
+        assert_eq!(podlang.len(), 6);
+        assert!(podlang[0].starts_with("HouseInputs1(inputs, private: s1, input1, input2)"));
+        assert!(podlang[1].starts_with("HouseInputs2(inputs, private: rest, s3, input3, input4)"));
+        assert!(podlang[1].contains("HouseInputs1(rest)"));
+        assert!(podlang[1].contains("SetInsert(s3, rest, input3)"));
+        assert!(podlang[1].contains("IsStone(input3)"));
+        assert!(podlang[2].starts_with("HouseInputs(inputs, private: rest, input5)"));
+        assert!(podlang[2].contains("SetInsert(inputs, rest, input5)"));
+        assert!(podlang[4].contains("HouseInputs(inputs)"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_recipes() {
+        // inputs must be defined first
+        assert!(RecipeRegistry::new(vec![recipe("axe", &["wood"]), recipe("wood", &[])]).is_err());
+        // blueprints are unique
+        assert!(RecipeRegistry::new(vec![recipe("wood", &[]), recipe("wood", &[])]).is_err());
+        // predicate names are unique
+        let mut wood_inputs = recipe("wood", &[]);
+        wood_inputs.predicate = "HouseInputs1".to_string();
+        assert!(
+            RecipeRegistry::new(vec![
+                wood_inputs,
+                recipe("house", &["wood", "wood", "wood"])
+            ])
+            .is_err()
+        );
+        // including the predicates derived from the recipe predicate
+        let mut wood_crafted = recipe("wood", &[]);
+        wood_crafted.predicate = "IsStoneCrafted".to_string();
+        assert!(RecipeRegistry::new(vec![wood_crafted, recipe("stone", &[])]).is_err());
+        // difficulty out of the range of HashInRange
+        let mut wood = recipe("wood", &[]);
+        wood.mining_max = u64::MAX;
+        assert!(RecipeRegistry::new(vec![wood]).is_err());
+
+        assert!(RecipeRegistry::new(vec![recipe("wood", &[]), recipe("axe", &["wood"])]).is_ok());
+    }
+}
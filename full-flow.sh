@@ -19,6 +19,25 @@ rm .env.bak
 
 mkdir -p tmp
 
+echo -e "unlocking the wallet for the commands run in tmux, creating it on the first run"
+# The password is read from WALLET_PASSWORD_FILE if set, or asked for once here
+if [[ -z "$WALLET_PASSWORD_FILE" ]]; then
+	WALLET_PASSWORD_FILE=$(cat .env | sed -n 's/^WALLET_PASSWORD_FILE[ \t]*=[ \t]*"\(.*\)".*/\1/p')
+fi
+if [[ -z "$WALLET_PASSWORD_FILE" ]]; then
+	WALLET_PASSWORD_FILE=$(mktemp)
+	read -r -s -p "Wallet password: " WALLET_PASSWORD
+	echo
+	printf '%s' "$WALLET_PASSWORD" > "$WALLET_PASSWORD_FILE"
+	unset WALLET_PASSWORD
+	TMP_PASSWORD_FILE="$WALLET_PASSWORD_FILE"
+fi
+export WALLET_PASSWORD_FILE
+cargo run --release -p app_cli -- init-wallet && cargo run --release -p app_cli -- unlock
+UNLOCKED=$?
+if [[ -n "$TMP_PASSWORD_FILE" ]]; then rm -f "$TMP_PASSWORD_FILE"; fi
+if [[ $UNLOCKED -ne 0 ]]; then exit 1; fi
+
 # set new variable to use tmux in a new env
 tmux="tmux -L ad-demo -f /dev/null"
 
@@ -35,25 +54,22 @@ $tmux send-keys -t fullflow:0.0 'RUST_LOG=synchronizer=debug cargo run --release
 $tmux send-keys -t fullflow:0.1 './wait-sync-epoch.sh 0' C-m
 $tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- craft  --recipe wood --output ./tmp/item-wood' C-m
 # commit the crafted item
-$tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- commit --input ./tmp/item-wood' C-m
+$tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- commit --wait --input ./tmp/item-wood' C-m
 # verify the crafted item
-$tmux send-keys -t fullflow:0.1 './wait-sync-epoch.sh 1' C-m
 $tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- verify --input ./tmp/item-wood' C-m
 
 # craft new stone item
 $tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- craft --recipe stone --output ./tmp/item-stone' C-m
 # commit the crafted item
-$tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- commit --input ./tmp/item-stone' C-m
+$tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- commit --wait --input ./tmp/item-stone' C-m
 # verify the crafted item
-$tmux send-keys -t fullflow:0.1 './wait-sync-epoch.sh 2' C-m
 $tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- verify --input ./tmp/item-stone' C-m
 
 # craft new axe item
 $tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- craft --recipe axe --input ./tmp/item-wood --input ./tmp/item-stone --output ./tmp/item-axe' C-m
 # commit the crafted item
-$tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- commit --input ./tmp/item-axe' C-m
+$tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- commit --wait --input ./tmp/item-axe' C-m
 # verify the crafted item
-$tmux send-keys -t fullflow:0.1 './wait-sync-epoch.sh 3' C-m
 $tmux send-keys -t fullflow:0.1 'RUST_LOG=app_cli=debug cargo run --release -p app_cli -- verify --input ./tmp/item-axe' C-m
 
 $tmux select-pane -t fullflow:0.1
@@ -32,6 +32,9 @@ macro_rules! op {
     (DictContains($dict:expr, $key:expr, $value:expr)) => {
         pod2::frontend::Operation::dict_contains($dict.clone(), $key.clone(), $value.clone())
     };
+    (DictNotContains($dict:expr, $key:expr)) => {
+        pod2::frontend::Operation::dict_not_contains($dict.clone(), $key.clone())
+    };
     (DictUpdate($dict:expr, $old_dict:expr, $key:expr, $value:expr)) => {
         pod2::frontend::Operation::dict_update(
             $dict.clone(),
@@ -0,0 +1,287 @@
+//! Typed async client for the synchronizer API, with retries, timeouts and verification of the
+//! Merkle proofs it serves.
+
+use std::time::Duration;
+
+use alloy::primitives::{Address, B256, TxHash};
+use backoff::{ExponentialBackoff, ExponentialBackoffBuilder};
+use pod2::{
+    backends::plonky2::primitives::merkletree::MerkleProof,
+    middleware::{Params, RawValue, Value, containers::Set},
+};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, de::DeserializeOwned};
+use tracing::{trace, warn};
+
+/// Timeout of a single request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum time spent retrying a request
+const MAX_RETRY_TIME: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error(transparent)]
+    Reqwest(#[from] reqwest::Error),
+
+    /// The requested resource doesn't exist (status 404)
+    #[error("NotFound: {0}")]
+    NotFound(String),
+
+    /// Unexpected status code
+    #[error("status {status}: {message}")]
+    Status { status: StatusCode, message: String },
+
+    /// A proof served by the synchronizer doesn't verify
+    #[error("invalid proof: {0}")]
+    InvalidProof(String),
+}
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// Where a payload was published
+#[derive(Debug, Clone, Deserialize)]
+pub struct Provenance {
+    pub slot: u32,
+    pub beacon_root: B256,
+    pub tx_hash: TxHash,
+    pub blob_index: u32,
+    pub payload_index: u32,
+    pub sender: Address,
+}
+
+/// The update applied by the synchronizer at an epoch
+#[derive(Debug, Clone, Deserialize)]
+pub struct EpochUpdate {
+    /// `None` for destructions, which only publish nullifiers
+    pub item: Option<RawValue>,
+    pub nullifiers: Vec<RawValue>,
+    pub provenance: Provenance,
+}
+
+/// A payload rejected by the synchronizer
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rejection {
+    pub slot: u32,
+    pub beacon_root: B256,
+    pub tx_hash: TxHash,
+    pub sender: Address,
+    pub blob_index: u32,
+    pub payload_index: Option<u32>,
+    pub reason: String,
+    pub message: String,
+}
+
+/// Verified state of a nullifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullifierStatus {
+    /// The nullifier wasn't published at `epoch`
+    Unspent { epoch: u64 },
+    /// The nullifier was published at or before `epoch`
+    Spent { epoch: u64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncClient {
+    base_url: String,
+    client: Client,
+    exp_backoff: ExponentialBackoff,
+    max_depth: usize,
+}
+
+fn hex(value: &RawValue) -> String {
+    // Skip the "0x" prefix
+    format!("{value:#}")[2..].to_string()
+}
+
+impl SyncClient {
+    pub fn new(base_url: &str, params: &Params) -> ClientResult<Self> {
+        let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        let exp_backoff = ExponentialBackoffBuilder::default()
+            .with_max_elapsed_time(Some(MAX_RETRY_TIME))
+            .build();
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+            exp_backoff,
+            max_depth: params.max_depth_mt_containers,
+        })
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> ClientResult<T> {
+        let url = format!("{}{}", self.base_url, path);
+        trace!(method = "GET", url, "Dispatching API request");
+        let resp = backoff::future::retry_notify(
+            self.exp_backoff.clone(),
+            || async {
+                let resp = self.client.get(&url).send().await.map_err(|e| {
+                    // The synchronizer may be restarting or overloaded
+                    if e.is_connect() || e.is_timeout() {
+                        backoff::Error::transient(e)
+                    } else {
+                        backoff::Error::permanent(e)
+                    }
+                })?;
+                match resp.status() {
+                    StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT => Err(backoff::Error::transient(
+                        resp.error_for_status().expect_err("error status"),
+                    )),
+                    _ => Ok(resp),
+                }
+            },
+            |error, duration: Duration| {
+                warn!(
+                    method = "GET",
+                    url,
+                    ?error,
+                    "Failed to send request. Retrying in {} seconds…",
+                    duration.as_secs()
+                );
+            },
+        )
+        .await?;
+
+        let status = resp.status();
+        if status == StatusCode::NOT_FOUND {
+            return Err(ClientError::NotFound(resp.text().await?));
+        }
+        if !status.is_success() {
+            return Err(ClientError::Status {
+                status,
+                message: resp.text().await?,
+            });
+        }
+        Ok(resp.json().await?)
+    }
+
+    /// Returns the latest epoch and its created items root.
+    pub async fn created_items_root(&self) -> ClientResult<(u64, RawValue)> {
+        self.get("/created_items_root").await
+    }
+
+    pub async fn created_items_root_at(&self, epoch: u64) -> ClientResult<RawValue> {
+        self.get(&format!("/created_items_root/{epoch}")).await
+    }
+
+    /// Returns the latest epoch and the items created at up to `limit` epochs after `epoch`, in
+    /// order.  Epochs of destructions have a `None` entry.
+    pub async fn created_items_since(
+        &self,
+        epoch: u64,
+        limit: Option<usize>,
+    ) -> ClientResult<(u64, Vec<Option<RawValue>>)> {
+        let query = limit.map(|limit| format!("?limit={limit}"));
+        self.get(&format!(
+            "/created_items_since/{epoch}{}",
+            query.unwrap_or_default()
+        ))
+        .await
+    }
+
+    /// Returns the epoch and the membership proof of `item` in the created items at it.
+    pub async fn created_item_proof(&self, item: &RawValue) -> ClientResult<(u64, MerkleProof)> {
+        self.get(&format!("/created_item/{}", hex(item))).await
+    }
+
+    pub async fn nullifiers_root_at(&self, epoch: u64) -> ClientResult<RawValue> {
+        self.get(&format!("/nullifiers_root/{epoch}")).await
+    }
+
+    /// Returns the epoch and the (non-)membership proof of `nullifier` in the nullifiers at it.
+    pub async fn nullifier_proof(&self, nullifier: &RawValue) -> ClientResult<(u64, MerkleProof)> {
+        self.get(&format!("/nullifier/{}/proof", hex(nullifier)))
+            .await
+    }
+
+    /// Returns the epoch at which `item` was created and the update applied at it, or `None`
+    /// if it hasn't been created.
+    pub async fn item_provenance(
+        &self,
+        item: &RawValue,
+    ) -> ClientResult<Option<(u64, EpochUpdate)>> {
+        match self.get(&format!("/item/{}/provenance", hex(item))).await {
+            Ok(provenance) => Ok(Some(provenance)),
+            Err(ClientError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn epoch(&self, epoch: u64) -> ClientResult<EpochUpdate> {
+        self.get(&format!("/epoch/{epoch}")).await
+    }
+
+    /// Returns the payloads sent in `tx_hash` that were rejected.
+    pub async fn rejections(&self, tx_hash: &TxHash) -> ClientResult<Vec<Rejection>> {
+        self.get(&format!("/rejections?tx={tx_hash}")).await
+    }
+
+    /// Verifies that `item` has been created, returning the epoch of the verified proof.
+    pub async fn verify_created_item(&self, item: &RawValue) -> ClientResult<u64> {
+        let (epoch, mtp) = self.created_item_proof(item).await?;
+        let root = self.created_items_root_at(epoch).await?;
+        Set::verify(self.max_depth, root.into(), &mtp, &Value::from(*item))
+            .map_err(|e| ClientError::InvalidProof(e.to_string()))?;
+        Ok(epoch)
+    }
+
+    /// Verifies whether `nullifier` has been published.
+    pub async fn verify_nullifier(&self, nullifier: &RawValue) -> ClientResult<NullifierStatus> {
+        let (epoch, mtp) = self.nullifier_proof(nullifier).await?;
+        let root = self.nullifiers_root_at(epoch).await?;
+        let value = Value::from(*nullifier);
+        if mtp.existence {
+            Set::verify(self.max_depth, root.into(), &mtp, &value)
+                .map_err(|e| ClientError::InvalidProof(e.to_string()))?;
+            Ok(NullifierStatus::Spent { epoch })
+        } else {
+            Set::verify_nonexistence(self.max_depth, root.into(), &mtp, &value)
+                .map_err(|e| ClientError::InvalidProof(e.to_string()))?;
+            Ok(NullifierStatus::Unspent { epoch })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_retries_refused_connection() {
+        // Port on which nothing listens until the first attempt has been refused
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client =
+            SyncClient::new(&format!("http://127.0.0.1:{port}"), &Params::default()).unwrap();
+        let request = tokio::spawn(async move { client.created_items_since(0, None).await });
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut received = Vec::new();
+        while !received.ends_with(b"\r\n\r\n") {
+            let mut buf = [0u8; 1024];
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed before the end of the request");
+            received.extend_from_slice(&buf[..n]);
+        }
+        let body = "[3,[]]";
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+
+        let (epoch, items) = request.await.unwrap().unwrap();
+        assert_eq!(epoch, 3);
+        assert!(items.is_empty());
+    }
+}
@@ -155,7 +155,6 @@ pub struct BlockHeaderMessage {
 pub struct HeadEventData {
     #[serde(deserialize_with = "deserialize_u32")]
     pub slot: u32,
-    #[allow(dead_code)]
     pub block: B256,
 }
 
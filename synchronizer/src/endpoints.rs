@@ -1,17 +1,43 @@
-use std::sync::Arc;
+use std::{convert::Infallible, str::FromStr, sync::Arc};
 
+use alloy::primitives::B256;
 use hex::FromHex;
 use pod2::middleware::{RawValue, Value};
-use warp::Filter;
+use serde::Deserialize;
+use warp::{Filter, http::StatusCode};
 
 use crate::Node;
 
+/// Maximum number of items returned by `GET /created_items_since/{epoch}`
+const MAX_CREATED_ITEMS_PAGE: usize = 4096;
+
 /// struct used to convert sqlx errors to warp errors
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct CustomError(pub String);
 impl warp::reject::Reject for CustomError {}
 
+/// Rejection for requests of things that don't exist in the state
+#[derive(Debug)]
+pub struct NotFound(pub String);
+impl warp::reject::Reject for NotFound {}
+
+/// Converts rejections into error responses with a matching status code
+pub(crate) async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::Reply, Infallible> {
+    let (code, message) = if let Some(NotFound(message)) = err.find() {
+        (StatusCode::NOT_FOUND, message.clone())
+    } else if let Some(CustomError(message)) = err.find() {
+        (StatusCode::BAD_REQUEST, message.clone())
+    } else if let Some(e) = err.find::<warp::reject::InvalidQuery>() {
+        (StatusCode::BAD_REQUEST, e.to_string())
+    } else if err.is_not_found() {
+        (StatusCode::NOT_FOUND, "Not found".to_string())
+    } else {
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", err))
+    };
+    Ok(warp::reply::with_status(message, code))
+}
+
 // HANDLERS:
 
 // GET /created_item/{item}
@@ -24,7 +50,7 @@ pub(crate) async fn handler_get_created_item(
     let mtp = state
         .created_items
         .prove(&Value::from(item))
-        .map_err(|e| CustomError(e.to_string()))?;
+        .map_err(|e| NotFound(e.to_string()))?;
     Ok(warp::reply::json(&(state.epoch, mtp)))
 }
 
@@ -36,6 +62,83 @@ pub(crate) async fn handler_get_created_items(
     Ok(warp::reply::json(&state.created_items))
 }
 
+#[derive(Debug, Deserialize)]
+pub(crate) struct PageQuery {
+    limit: Option<usize>,
+}
+
+// GET /created_items_since/{epoch}?limit={limit}
+// Returns the latest epoch and the items created after `epoch`, in order, so that the item at
+// index `i` was created at epoch `epoch + i + 1`.  Epochs of destructions, which don't create
+// an item, have a `null` entry.  At most `limit` entries are returned.
+pub(crate) async fn handler_get_created_items_since(
+    epoch: u64,
+    query: PageQuery,
+    node: Arc<Node>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let state = node.state.read().unwrap();
+    if epoch > state.epoch {
+        return Err(NotFound(format!("Invalid epoch: {}", epoch)).into());
+    }
+    let limit = query
+        .limit
+        .unwrap_or(MAX_CREATED_ITEMS_PAGE)
+        .min(MAX_CREATED_ITEMS_PAGE);
+    let items: Vec<_> = state.epochs[epoch as usize..]
+        .iter()
+        .take(limit)
+        .map(|update| update.item)
+        .collect();
+    Ok(warp::reply::json(&(state.epoch, items)))
+}
+
+// GET /item/{item}/provenance
+// Returns the epoch at which the item was created, and the update applied at it, including
+// where the payload was published.
+pub(crate) async fn handler_get_item_provenance(
+    item_str: String,
+    node: Arc<Node>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let item = RawValue::from_hex(&item_str).map_err(|e| CustomError(e.to_string()))?;
+    let state = node.state.read().unwrap();
+    let epoch = *state
+        .item_epochs
+        .get(&item)
+        .ok_or(NotFound(format!("Item not found: {}", item)))?;
+    Ok(warp::reply::json(&(
+        epoch,
+        &state.epochs[epoch as usize - 1],
+    )))
+}
+
+// GET /epoch/{epoch}
+// Returns the update applied at `epoch`, including where its payload was published.
+pub(crate) async fn handler_get_epoch(
+    epoch: u64,
+    node: Arc<Node>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let state = node.state.read().unwrap();
+    (1..=state.epoch)
+        .contains(&epoch)
+        .then(|| warp::reply::json(&state.epochs[epoch as usize - 1]))
+        .ok_or(NotFound(format!("Invalid epoch: {}", epoch)).into())
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct RejectionsQuery {
+    tx: String,
+}
+
+// GET /rejections?tx={tx_hash}
+// Returns the payloads sent in the transaction that were rejected, with the reason.
+pub(crate) async fn handler_get_rejections(
+    query: RejectionsQuery,
+    node: Arc<Node>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let tx_hash = B256::from_str(&query.tx).map_err(|e| CustomError(e.to_string()))?;
+    Ok(warp::reply::json(&node.rejections.by_tx(&tx_hash)))
+}
+
 // GET /created_items_root
 pub(crate) async fn handler_get_latest_created_items_root(
     node: Arc<Node>,
@@ -55,7 +158,7 @@ pub(crate) async fn handler_get_created_items_root(
     let state = node.state.read().unwrap();
     (state.epoch >= epoch)
         .then(|| warp::reply::json(&state.created_items_roots[epoch as usize]))
-        .ok_or(CustomError(format!("Invalid epoch: {}", epoch)).into())
+        .ok_or(NotFound(format!("Invalid epoch: {}", epoch)).into())
 }
 
 // GET /nullifier/{nullifier}
@@ -65,21 +168,70 @@ pub(crate) async fn handler_get_nullifier(
 ) -> Result<impl warp::Reply, warp::Rejection> {
     let nullifier = RawValue::from_hex(&nullifier_str).map_err(|e| CustomError(e.to_string()))?;
     let state = node.state.read().unwrap();
-    let exists = state.nullifiers.contains(&nullifier);
+    let exists = state.nullifiers.contains(&Value::from(nullifier));
     Ok(warp::reply::json(&exists))
 }
 
+// GET /nullifier/{nullifier}/proof
+// Returns a membership proof if the nullifier has been published, or a non-membership proof
+// otherwise.  `MerkleProof.existence` tells which one.
+pub(crate) async fn handler_get_nullifier_proof(
+    nullifier_str: String,
+    node: Arc<Node>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let nullifier = RawValue::from_hex(&nullifier_str).map_err(|e| CustomError(e.to_string()))?;
+    let nullifier = Value::from(nullifier);
+    let state = node.state.read().unwrap();
+    let mtp = if state.nullifiers.contains(&nullifier) {
+        state.nullifiers.prove(&nullifier)
+    } else {
+        state.nullifiers.prove_nonexistence(&nullifier)
+    }
+    .map_err(|e| CustomError(e.to_string()))?;
+    Ok(warp::reply::json(&(state.epoch, mtp)))
+}
+
+// GET /nullifiers_root
+pub(crate) async fn handler_get_latest_nullifiers_root(
+    node: Arc<Node>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let state = node.state.read().unwrap();
+    Ok(warp::reply::json(&(
+        state.epoch,
+        state.nullifiers_roots.last().unwrap(),
+    )))
+}
+
+// GET /nullifiers_root/{epoch}
+pub(crate) async fn handler_get_nullifiers_root(
+    epoch: u64,
+    node: Arc<Node>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let state = node.state.read().unwrap();
+    (state.epoch >= epoch)
+        .then(|| warp::reply::json(&state.nullifiers_roots[epoch as usize]))
+        .ok_or(NotFound(format!("Invalid epoch: {}", epoch)).into())
+}
+
 // ROUTES:
 
 // build the routes
 pub(crate) fn routes(
     node: Arc<Node>,
-) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+) -> impl Filter<Extract = impl warp::Reply, Error = Infallible> + Clone {
     get_created_item(node.clone())
         .or(get_created_items(node.clone()))
+        .or(get_created_items_since(node.clone()))
+        .or(get_item_provenance(node.clone()))
+        .or(get_epoch(node.clone()))
+        .or(get_rejections(node.clone()))
         .or(get_latest_created_items_root(node.clone()))
         .or(get_created_items_root(node.clone()))
-        .or(get_nullifier(node))
+        .or(get_nullifier(node.clone()))
+        .or(get_nullifier_proof(node.clone()))
+        .or(get_latest_nullifiers_root(node.clone()))
+        .or(get_nullifiers_root(node))
+        .recover(handle_rejection)
 }
 
 fn get_created_item(
@@ -104,6 +256,52 @@ fn get_created_items(
         .and_then(handler_get_created_items)
 }
 
+fn get_created_items_since(
+    node: Arc<Node>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let node_filter = warp::any().map(move || node.clone());
+
+    warp::path!("created_items_since" / u64)
+        .and(warp::get())
+        .and(warp::query::<PageQuery>())
+        .and(node_filter)
+        .and_then(handler_get_created_items_since)
+}
+
+fn get_item_provenance(
+    node: Arc<Node>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let node_filter = warp::any().map(move || node.clone());
+
+    warp::path!("item" / String / "provenance")
+        .and(warp::get())
+        .and(node_filter)
+        .and_then(handler_get_item_provenance)
+}
+
+fn get_epoch(
+    node: Arc<Node>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let node_filter = warp::any().map(move || node.clone());
+
+    warp::path!("epoch" / u64)
+        .and(warp::get())
+        .and(node_filter)
+        .and_then(handler_get_epoch)
+}
+
+fn get_rejections(
+    node: Arc<Node>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let node_filter = warp::any().map(move || node.clone());
+
+    warp::path!("rejections")
+        .and(warp::get())
+        .and(warp::query::<RejectionsQuery>())
+        .and(node_filter)
+        .and_then(handler_get_rejections)
+}
+
 fn get_latest_created_items_root(
     node: Arc<Node>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
@@ -136,3 +334,36 @@ fn get_nullifier(
         .and(node_filter)
         .and_then(handler_get_nullifier)
 }
+
+fn get_nullifier_proof(
+    node: Arc<Node>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let node_filter = warp::any().map(move || node.clone());
+
+    warp::path!("nullifier" / String / "proof")
+        .and(warp::get())
+        .and(node_filter)
+        .and_then(handler_get_nullifier_proof)
+}
+
+fn get_latest_nullifiers_root(
+    node: Arc<Node>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let node_filter = warp::any().map(move || node.clone());
+
+    warp::path!("nullifiers_root")
+        .and(warp::get())
+        .and(node_filter)
+        .and_then(handler_get_latest_nullifiers_root)
+}
+
+fn get_nullifiers_root(
+    node: Arc<Node>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let node_filter = warp::any().map(move || node.clone());
+
+    warp::path!("nullifiers_root" / u64)
+        .and(warp::get())
+        .and(node_filter)
+        .and_then(handler_get_nullifiers_root)
+}
@@ -20,12 +20,19 @@ pub(crate) async fn get_blobs(beacon_url: &str, block_id: u64) -> Result<Vec<Blo
 /// Extracts bytes from a blob in the 'simple' encoding.
 pub fn bytes_from_simple_blob(blob_bytes: &[u8]) -> Result<Vec<u8>> {
     // Blob = [0x00] ++ 8_BYTE_LEN ++ [0x00,...,0x00] ++ X.
-    let data_len = u64::from_be_bytes(std::array::from_fn(|i| blob_bytes[1 + i])) as usize;
+    if blob_bytes.is_empty() || blob_bytes.len() % FIELD_ELEMENT_BYTES_USIZE != 0 {
+        return Err(anyhow!(
+            "Blob length {} is not a positive multiple of {}.",
+            blob_bytes.len(),
+            FIELD_ELEMENT_BYTES_USIZE
+        ));
+    }
+    let data_len = u64::from_be_bytes(std::array::from_fn(|i| blob_bytes[1 + i]));
 
     // Sanity check: Blob must be able to accommodate the specified data length.
     let max_data_len =
         (blob_bytes.len() / FIELD_ELEMENT_BYTES_USIZE - 1) * (FIELD_ELEMENT_BYTES_USIZE - 1);
-    if data_len > max_data_len {
+    if data_len > max_data_len as u64 {
         return Err(anyhow!(
             "Given blob of length {} cannot accommodate {} bytes.",
             blob_bytes.len(),
@@ -37,7 +44,7 @@ pub fn bytes_from_simple_blob(blob_bytes: &[u8]) -> Result<Vec<u8>> {
         .chunks(FIELD_ELEMENT_BYTES_USIZE)
         .skip(1)
         .flat_map(|chunk| chunk[1..].to_vec())
-        .take(data_len)
+        .take(data_len as usize)
         .collect())
 }
 
@@ -54,4 +61,19 @@ mod tests {
         // println!("{:?}", _blobs); // commented out since it prints more than 10k lines
         Ok(())
     }
+
+    #[test]
+    fn test_bytes_from_simple_blob_malformed() {
+        assert!(bytes_from_simple_blob(&[]).is_err());
+        assert!(bytes_from_simple_blob(&[0; 8]).is_err());
+        assert!(bytes_from_simple_blob(&[0; 33]).is_err());
+
+        let mut blob = [0u8; 2 * FIELD_ELEMENT_BYTES_USIZE];
+        blob[1..9].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(bytes_from_simple_blob(&blob).is_err());
+
+        blob[1..9].copy_from_slice(&3u64.to_be_bytes());
+        blob[33..36].copy_from_slice(&[1, 2, 3]);
+        assert_eq!(bytes_from_simple_blob(&blob).unwrap(), vec![1, 2, 3]);
+    }
 }
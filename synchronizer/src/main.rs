@@ -4,9 +4,9 @@ use std::{
     fs::{File, create_dir_all, read_dir, rename},
     io,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
     time::Duration,
 };
 
@@ -26,9 +26,10 @@ use chrono::{DateTime, Utc};
 use commitlib::predicates::CommitPredicates;
 use common::{
     ProofType, load_dotenv, log_init,
-    payload::{Payload, PayloadProof},
+    payload::{Payload, PayloadKind, PayloadProof},
     shrink::ShrunkMainPodSetup,
 };
+use futures::StreamExt;
 use plonky2::plonk::proof::CompressedProofWithPublicInputs;
 use pod2::{
     backends::plonky2::{
@@ -43,17 +44,31 @@ use pod2::{
         VerifierCircuitData, containers::Set,
     },
 };
+use rejections::{Journal, RejectExt, Rejected, Rejection, RejectionReason};
+use reqwest_eventsource::Event;
+use serde::{Deserialize, Serialize};
+use snapshot::SnapshotOrigin;
 use synchronizer::{
     bytes_from_simple_blob,
     clients::beacon::{
         self, BeaconClient,
-        types::{Blob, BlockHeader, BlockId},
+        types::{Blob, BlockHeader, BlockId, FinalizedCheckpointEventData, HeadEventData, Topic},
     },
 };
-use tokio::{runtime::Runtime, time::sleep};
+use tokio::{
+    runtime::Runtime,
+    time::{sleep, timeout},
+};
 use tracing::{debug, info, trace};
 
 pub mod endpoints;
+mod rejections;
+mod snapshot;
+
+// A new head is expected every slot, so a silent stream for longer than this is considered
+// disconnected.
+const EVENTS_TIMEOUT: Duration = Duration::from_secs(60);
+const EVENTS_RECONNECT_DELAY: Duration = Duration::from_secs(5);
 
 pub fn cache_get_shrunk_main_pod_circuit_data(
     params: &Params,
@@ -80,6 +95,12 @@ pub struct Config {
     pub rpc_url: String,
     // The path to the ad blob storage directory
     pub blobs_path: String,
+    // The path to the state snapshots directory
+    pub snapshots_path: String,
+    // The path to the rejected payloads journal
+    pub rejections_path: String,
+    // Number of slots between state snapshots
+    pub snapshot_interval: u32,
     // The slot where the DO updates begins
     pub do_genesis_slot: u32,
     // The address that receives DO update via blobs
@@ -100,6 +121,9 @@ impl Config {
             beacon_url: var("BEACON_URL")?,
             rpc_url: var("RPC_URL")?,
             blobs_path: var("BLOBS_PATH")?,
+            snapshots_path: var("SNAPSHOTS_PATH")?,
+            rejections_path: var("REJECTIONS_PATH")?,
+            snapshot_interval: u32::from_str(&var("SNAPSHOT_INTERVAL")?)?,
             do_genesis_slot: u32::from_str(&var("DO_GENESIS_SLOT")?)?,
             to_addr: Address::from_str(&var("TO_ADDR")?)?,
             request_rate: u64::from_str(&var("REQUEST_RATE")?)?,
@@ -108,12 +132,184 @@ impl Config {
     }
 }
 
-#[derive(Debug)]
+/// Identifies a processed beacon block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct BlockRef {
+    root: B256,
+    slot: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct State {
+    // Last block whose payloads are included in this state
+    block: Option<BlockRef>,
     epoch: u64,
     created_items_roots: Vec<RawValue>,
     created_items: Set,
-    nullifiers: HashSet<RawValue>,
+    // Update applied at each epoch, starting at epoch 1
+    epochs: Vec<StateUpdate>,
+    // Epoch at which each item was created.  Rebuilt from `epochs` when loading a snapshot.
+    #[serde(skip)]
+    item_epochs: HashMap<RawValue, u64>,
+    // Root of `nullifiers` at each epoch
+    nullifiers_roots: Vec<RawValue>,
+    nullifiers: Set,
+}
+
+/// Where a payload was published
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Provenance {
+    slot: u32,
+    beacon_root: B256,
+    tx_hash: B256,
+    blob_index: u32,
+    // Index of the payload within the blob
+    payload_index: u32,
+    sender: Address,
+}
+
+/// The changes to the `State` introduced by a valid payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StateUpdate {
+    // `None` for destructions, which only publish nullifiers
+    item: Option<RawValue>,
+    nullifiers: Vec<RawValue>,
+    provenance: Provenance,
+}
+
+impl State {
+    fn new(params: &Params) -> Self {
+        let created_items = Set::new(params.max_depth_mt_containers, HashSet::new()).unwrap();
+        let nullifiers = Set::new(params.max_depth_mt_containers, HashSet::new()).unwrap();
+        Self {
+            block: None,
+            epoch: 0,
+            // initialize the `created_items_root` (which is an empty root
+            // (0x00...), so that when new items are crafted from scratch, their
+            // `payload.created_items_root` (which is 0x00... since it is a
+            // from-scratch item) is accepted as a "valid" one, since it appears
+            // at the `created_items_root`.
+            created_items_roots: vec![RawValue::from(created_items.commitment())],
+            created_items,
+            epochs: Vec::new(),
+            item_epochs: HashMap::new(),
+            nullifiers_roots: vec![RawValue::from(nullifiers.commitment())],
+            nullifiers,
+        }
+    }
+
+    /// Checks that the payload can be applied on top of this state.  The proof is not checked.
+    fn check_payload(&self, payload: &Payload) -> Result<(), Rejected> {
+        // Check the proof is using an official createdItems set
+        if !self
+            .created_items_roots
+            .contains(&payload.created_items_root)
+        {
+            return Err(Rejected::new(
+                RejectionReason::StaleRoot,
+                anyhow!(
+                    "created_items_root {} not in created_items_roots",
+                    payload.created_items_root
+                ),
+            ));
+        }
+
+        match payload.kind.item() {
+            // Check that output is unique
+            Some(item) => {
+                if self.created_items.contains(&Value::from(item)) {
+                    return Err(Rejected::new(
+                        RejectionReason::DuplicateItem,
+                        anyhow!("item {} exists in created_items", item),
+                    ));
+                }
+            }
+            // Check that a destruction destroys something
+            None => {
+                if payload.nullifiers.is_empty() {
+                    return Err(Rejected::new(
+                        RejectionReason::EmptyDestruction,
+                        anyhow!("destruction without nullifiers"),
+                    ));
+                }
+            }
+        }
+
+        // Check that inputs are unique
+        for nullifier in &payload.nullifiers {
+            if self.nullifiers.contains(&Value::from(*nullifier)) {
+                return Err(Rejected::new(
+                    RejectionReason::SpentNullifier,
+                    anyhow!("nullifier {} exists in nullifiers", nullifier),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, update: &StateUpdate) {
+        // Register nullifiers
+        for nullifier in &update.nullifiers {
+            self.nullifiers.insert(&Value::from(*nullifier)).unwrap();
+        }
+        // Register item
+        if let Some(item) = update.item {
+            self.created_items.insert(&Value::from(item)).unwrap();
+        }
+
+        self.epoch += 1;
+        let created_items_root = self.created_items.commitment();
+        self.created_items_roots
+            .push(RawValue::from(created_items_root));
+        self.nullifiers_roots
+            .push(RawValue::from(self.nullifiers.commitment()));
+        if let Some(item) = update.item {
+            self.item_epochs.insert(item, self.epoch);
+        }
+        self.epochs.push(update.clone());
+    }
+
+    /// Rebuilds the indexes that aren't serialized.
+    fn rebuild_index(&mut self) {
+        self.item_epochs = self
+            .epochs
+            .iter()
+            .zip(1..)
+            .filter_map(|(update, epoch)| Some((update.item?, epoch)))
+            .collect();
+    }
+
+    fn apply_block(&mut self, block: &PendingBlock) {
+        for update in &block.updates {
+            self.apply(update);
+        }
+        self.block = Some(block.block);
+    }
+}
+
+/// A blob sent to `cfg.to_addr`, with the transaction that carried it
+#[derive(Debug)]
+struct DoBlob {
+    blob: Blob,
+    tx_hash: B256,
+    sender: Address,
+}
+
+/// A processed block that is not finalized yet
+#[derive(Debug)]
+struct PendingBlock {
+    block: BlockRef,
+    updates: Vec<StateUpdate>,
+}
+
+/// The chain of processed blocks on top of the finalized `State`
+#[derive(Debug)]
+struct Pending {
+    blocks: Vec<PendingBlock>,
+    // The finalized state with all the `blocks` applied.  Payloads are validated against it.
+    head: State,
+    // Next slot to process
+    next_slot: u32,
 }
 
 #[derive(Debug)]
@@ -127,8 +323,17 @@ struct Node {
     common_circuit_data: CommonCircuitData,
     verifier_circuit_data: VerifierCircuitData,
     pred_commit_creation: CustomPredicateRef,
+    pred_commit_destruction: CustomPredicateRef,
+    pred_commit_transfer: CustomPredicateRef,
     // Mutable state
+    // Finalized state.  This is the only state served to users, so that items never vanish.
     state: RwLock<State>,
+    // Non-finalized blocks on top of `state`.  Lock it before `state` when taking both.
+    pending: Mutex<Pending>,
+    // Latest finalized block reported by the beacon node
+    finalized: Mutex<Option<BlockRef>>,
+    // Payloads that were not applied, with the reason
+    rejections: Journal,
 }
 
 impl Node {
@@ -152,17 +357,23 @@ impl Node {
         let (common_circuit_data, verifier_circuit_data) =
             &*cache_get_shrunk_main_pod_circuit_data(&params);
 
-        let created_items = Set::new(params.max_depth_mt_containers, HashSet::new()).unwrap();
-        let state = State {
-            epoch: 0,
-            // initialize the `created_items_root` (which is an empty root
-            // (0x00...), so that when new items are crafted from scratch, their
-            // `payload.created_items_root` (which is 0x00... since it is a
-            // from-scratch item) is accepted as a "valid" one, since it appears
-            // at the `created_items_root`.
-            created_items_roots: vec![RawValue::from(created_items.commitment())],
-            created_items,
-            nullifiers: HashSet::new(),
+        let origin = Self::snapshot_origin(&cfg);
+        let state = match snapshot::load_latest(Path::new(&cfg.snapshots_path), &origin)? {
+            Some(mut state) => {
+                info!(
+                    "Resuming from snapshot at block {:?} (epoch {})",
+                    state.block, state.epoch
+                );
+                state.rebuild_index();
+                state
+            }
+            None => State::new(&params),
+        };
+        let rejections = Journal::open(Path::new(&cfg.rejections_path))?;
+        let pending = Pending {
+            blocks: Vec::new(),
+            head: state.clone(),
+            next_slot: Self::slot_after(&cfg, &state),
         };
         Ok(Self {
             cfg,
@@ -173,10 +384,134 @@ impl Node {
             common_circuit_data: (**common_circuit_data).clone(),
             verifier_circuit_data: (**verifier_circuit_data).clone(),
             pred_commit_creation: commit_predicates.commit_creation,
+            pred_commit_destruction: commit_predicates.commit_destruction,
+            pred_commit_transfer: commit_predicates.commit_transfer,
             state: RwLock::new(state),
+            pending: Mutex::new(pending),
+            finalized: Mutex::new(None),
+            rejections,
         })
     }
 
+    fn snapshot_origin(cfg: &Config) -> SnapshotOrigin {
+        SnapshotOrigin {
+            do_genesis_slot: cfg.do_genesis_slot,
+            to_addr: cfg.to_addr,
+        }
+    }
+
+    /// Returns the first slot that hasn't been applied to `state`
+    fn slot_after(cfg: &Config, state: &State) -> u32 {
+        state
+            .block
+            .map(|block| block.slot + 1)
+            .unwrap_or(cfg.do_genesis_slot)
+    }
+
+    /// Stores a snapshot of the finalized state.
+    fn store_snapshot(&self) -> Result<()> {
+        let state = self.state.read().expect("lock");
+        snapshot::store(
+            Path::new(&self.cfg.snapshots_path),
+            &Self::snapshot_origin(&self.cfg),
+            &state,
+        )
+    }
+
+    fn finalized_slot(&self) -> Option<u32> {
+        self.state
+            .read()
+            .expect("lock")
+            .block
+            .map(|block| block.slot)
+    }
+
+    fn next_slot(&self) -> u32 {
+        self.pending.lock().expect("lock").next_slot
+    }
+
+    /// Records that `slot` has no block.
+    fn skip_slot(&self, slot: u32) {
+        let mut pending = self.pending.lock().expect("lock");
+        if pending.next_slot == slot {
+            pending.next_slot = slot + 1;
+        }
+    }
+
+    /// Discards all the pending blocks but the first `keep` ones and rebuilds the head state.
+    fn rollback(&self, pending: &mut Pending, keep: usize) {
+        let state = self.state.read().expect("lock");
+        pending.blocks.truncate(keep);
+        let mut head = state.clone();
+        for block in &pending.blocks {
+            head.apply_block(block);
+        }
+        pending.next_slot = Self::slot_after(&self.cfg, &head);
+        pending.head = head;
+    }
+
+    /// Records the latest finalized block from the beacon node.
+    async fn update_finalized(&self, block_id: BlockId) -> Result<()> {
+        let header = self
+            .beacon_cli
+            .get_block_header(block_id.clone())
+            .await?
+            .with_context(|| format!("Finalized block {} not found", block_id))?;
+        debug!("finalized block {} at slot {}", header.root, header.slot);
+        *self.finalized.lock().expect("lock") = Some(BlockRef {
+            root: header.root,
+            slot: header.slot,
+        });
+        Ok(())
+    }
+
+    /// Moves the pending blocks up to the latest finalized block into the finalized state.
+    /// Returns whether the finalized state changed.
+    fn promote_finalized(&self) -> bool {
+        let Some(finalized) = *self.finalized.lock().expect("lock") else {
+            return false;
+        };
+        let mut pending = self.pending.lock().expect("lock");
+        let Some(index) = pending
+            .blocks
+            .iter()
+            .position(|block| block.block == finalized)
+        else {
+            let first_slot = pending.blocks.first().map(|block| block.block.slot);
+            let tip_slot = pending.head.block.map(|block| block.slot);
+            if let (Some(first_slot), Some(tip_slot)) = (first_slot, tip_slot)
+                && (first_slot..=tip_slot).contains(&finalized.slot)
+            {
+                // The finalized block should be among the pending ones, so they belong to an
+                // abandoned fork.
+                info!(
+                    "finalized block {:?} not in pending blocks, rolling back {} blocks",
+                    finalized,
+                    pending.blocks.len()
+                );
+                self.rollback(&mut pending, 0);
+            }
+            return false;
+        };
+
+        let mut state = self.state.write().expect("lock");
+        for block in pending.blocks.drain(..=index) {
+            for update in &block.updates {
+                state.apply(update);
+                info!(
+                    "state update: epoch={}, created_items.len={}, nullifiers.len={}, created_items_root={}, ",
+                    state.epoch,
+                    state.created_items.set().len(),
+                    state.nullifiers.set().len(),
+                    state.created_items_roots.last().expect("not empty"),
+                );
+            }
+            state.block = Some(block.block);
+        }
+        debug!("finalized state at slot {}", finalized.slot);
+        true
+    }
+
     fn slot_dir(&self, slot: u32) -> PathBuf {
         let slot_hi = slot / 1_000_000;
         let slot_mid = (slot - slot_hi * 1_000_000) / 1_000;
@@ -274,10 +609,166 @@ impl Node {
         }
     }
 
-    async fn process_beacon_block_header(
-        &self,
-        beacon_block_header: &BlockHeader,
-    ) -> Result<Option<()>> {
+    /// Processes the block at `beacon_block_header`, first rolling back and replaying the
+    /// pending blocks if it doesn't extend the last processed block.
+    async fn process_beacon_block_header(&self, beacon_block_header: &BlockHeader) -> Result<()> {
+        let tip = self.pending.lock().expect("lock").head.block;
+        if let Some(tip) = tip
+            && tip.root != beacon_block_header.parent_root
+        {
+            self.reorg(beacon_block_header).await?;
+        }
+        self.process_beacon_block(beacon_block_header).await
+    }
+
+    /// Finds the common ancestor of `beacon_block_header` and the processed blocks, rolls back
+    /// the pending blocks after it and processes the new branch up to `beacon_block_header`
+    /// (exclusive).
+    async fn reorg(&self, beacon_block_header: &BlockHeader) -> Result<()> {
+        let (finalized, pending_roots, first_slot) = {
+            let pending = self.pending.lock().expect("lock");
+            let state = self.state.read().expect("lock");
+            let pending_roots: Vec<_> = pending.blocks.iter().map(|b| b.block.root).collect();
+            (
+                state.block,
+                pending_roots,
+                Self::slot_after(&self.cfg, &state),
+            )
+        };
+
+        let mut branch = Vec::new();
+        let mut parent_root = beacon_block_header.parent_root;
+        let keep = loop {
+            if let Some(index) = pending_roots.iter().position(|root| *root == parent_root) {
+                break index + 1;
+            }
+            if finalized.map(|block| block.root) == Some(parent_root) {
+                break 0;
+            }
+            let parent = self
+                .beacon_cli
+                .get_block_header(BlockId::Hash(parent_root))
+                .await?
+                .with_context(|| format!("Block {} not found", parent_root))?;
+            if parent.slot < first_slot {
+                if let Some(finalized) = finalized {
+                    bail!(
+                        "block {} forks before the finalized block {:?}",
+                        beacon_block_header.root,
+                        finalized
+                    );
+                }
+                // The fork point is before the genesis slot
+                break 0;
+            }
+            parent_root = parent.parent_root;
+            branch.push(parent);
+        };
+
+        info!(
+            "reorg at slot {}: rolling back {} blocks, applying {} blocks",
+            beacon_block_header.slot,
+            pending_roots.len() - keep,
+            branch.len()
+        );
+        {
+            let mut pending = self.pending.lock().expect("lock");
+            self.rollback(&mut pending, keep);
+        }
+        for header in branch.iter().rev() {
+            self.process_beacon_block(header).await?;
+        }
+        Ok(())
+    }
+
+    /// Validates the DO payloads of a block on top of the head state and appends it to the
+    /// pending blocks.
+    async fn process_beacon_block(&self, beacon_block_header: &BlockHeader) -> Result<()> {
+        let slot = beacon_block_header.slot;
+        let do_blobs = self.get_do_blobs(beacon_block_header).await?;
+
+        let rejection = |do_blob: &DoBlob, payload_index, e: Rejected| Rejection {
+            slot,
+            beacon_root: beacon_block_header.root,
+            tx_hash: do_blob.tx_hash,
+            sender: do_blob.sender,
+            blob_index: do_blob.blob.index,
+            payload_index,
+            reason: e.reason,
+            message: format!("{:#}", e.source),
+        };
+        // Decode the payloads and verify their proofs before locking the pending blocks, so that
+        // the endpoints aren't blocked during the verification
+        let mut verified = Vec::new();
+        for do_blob in &do_blobs {
+            let payloads = match self.decode_do_blob(&do_blob.blob) {
+                Ok(payloads) => payloads,
+                Err(e) => {
+                    info!("Invalid do_blob: {:?}", e);
+                    self.rejections.record(rejection(
+                        do_blob,
+                        None,
+                        Rejected::new(RejectionReason::DecodeError, e),
+                    ));
+                    continue;
+                }
+            };
+            for (payload_index, payload) in (0..).zip(payloads) {
+                match self.verify_payload(&payload) {
+                    Ok(()) => verified.push((do_blob, payload_index, payload)),
+                    Err(e) => {
+                        info!(
+                            "Invalid payload at blob_index {}, payload_index {}: {}",
+                            do_blob.blob.index, payload_index, e
+                        );
+                        self.rejections
+                            .record(rejection(do_blob, Some(payload_index), e));
+                    }
+                }
+            }
+        }
+
+        let mut pending = self.pending.lock().expect("lock");
+        let mut updates = Vec::new();
+        for (do_blob, payload_index, payload) in verified {
+            let provenance = Provenance {
+                slot,
+                beacon_root: beacon_block_header.root,
+                tx_hash: do_blob.tx_hash,
+                blob_index: do_blob.blob.index,
+                payload_index,
+                sender: do_blob.sender,
+            };
+            match self.apply_payload(&mut pending.head, payload, provenance) {
+                Ok(update) => {
+                    info!(
+                        "Valid payload at slot {}, blob_index {}, payload_index {}!",
+                        slot, do_blob.blob.index, payload_index
+                    );
+                    updates.push(update);
+                }
+                Err(e) => {
+                    info!(
+                        "Invalid payload at blob_index {}, payload_index {}: {}",
+                        do_blob.blob.index, payload_index, e
+                    );
+                    self.rejections
+                        .record(rejection(do_blob, Some(payload_index), e));
+                }
+            };
+        }
+        let block = BlockRef {
+            root: beacon_block_header.root,
+            slot,
+        };
+        pending.head.block = Some(block);
+        pending.blocks.push(PendingBlock { block, updates });
+        pending.next_slot = slot + 1;
+        Ok(())
+    }
+
+    /// Returns the blobs of the block sent to `cfg.to_addr`, in order.
+    async fn get_do_blobs(&self, beacon_block_header: &BlockHeader) -> Result<Vec<DoBlob>> {
         let beacon_block_root = beacon_block_header.root;
         let slot = beacon_block_header.slot;
 
@@ -289,14 +780,14 @@ impl Node {
             Some(block) => block,
             None => {
                 debug!("slot {} has empty block", slot);
-                return Ok(None);
+                return Ok(Vec::new());
             }
         };
         let execution_payload = match beacon_block.execution_payload {
             Some(payload) => payload,
             None => {
                 debug!("slot {} has no execution payload", slot);
-                return Ok(None);
+                return Ok(Vec::new());
             }
         };
         debug!(
@@ -317,7 +808,7 @@ impl Node {
         };
         if !has_kzg_blob_commitments {
             debug!("slot {} has no blobs", slot);
-            return Ok(None);
+            return Ok(Vec::new());
         }
 
         let execution_block_hash = execution_payload.block_hash;
@@ -347,7 +838,7 @@ impl Node {
         };
 
         if indexed_do_blob_txs.is_empty() {
-            return Ok(None);
+            return Ok(Vec::new());
         }
 
         let txs_blobs_vhs: Vec<B256> = indexed_do_blob_txs
@@ -359,66 +850,39 @@ impl Node {
             })
             .cloned()
             .collect();
-        let blobs = self.get_blobs(slot, &txs_blobs_vhs).await?;
+        let mut blobs = self.get_blobs(slot, &txs_blobs_vhs).await?;
 
+        let mut do_blobs = Vec::new();
         for (_tx_index, tx) in indexed_do_blob_txs {
             let tx = tx.as_recovered();
             let hash = tx.hash();
             let from = tx.signer();
             let to = tx.to();
-            let tx_blobs: Vec<_> = tx
-                .blob_versioned_hashes()
-                .expect("tx has blobs")
-                .iter()
-                .map(|blob_versioned_hash| &blobs[blob_versioned_hash])
-                .collect();
             trace!(?hash, ?from, ?to);
-
-            for blob in tx_blobs.iter() {
-                match self.process_do_blob(blob).await {
-                    Ok(_) => {
-                        info!("Valid do_blob at slot {}, blob_index {}!", slot, blob.index);
-                    }
-                    Err(e) => {
-                        info!("Invalid do_blob: {:?}", e);
-                        continue;
-                    }
-                };
-            }
+            do_blobs.extend(
+                tx.blob_versioned_hashes()
+                    .expect("tx has blobs")
+                    .iter()
+                    .filter_map(|blob_versioned_hash| blobs.remove(blob_versioned_hash))
+                    .map(|blob| DoBlob {
+                        blob,
+                        tx_hash: *hash,
+                        sender: from,
+                    }),
+            );
         }
-        Ok(Some(()))
+        Ok(do_blobs)
     }
 
-    async fn process_do_blob(&self, blob: &Blob) -> Result<()> {
+    /// Decodes the payloads in `blob`, in any supported payload version.
+    fn decode_do_blob(&self, blob: &Blob) -> Result<Vec<Payload>> {
         let bytes =
             bytes_from_simple_blob(blob.blob.inner()).context("Invalid byte encoding in blob")?;
-        let payload = Payload::from_bytes(&bytes, &self.common_circuit_data)?;
-
-        let mut state = self.state.write().expect("lock");
-
-        // Check the proof is using an official createdItems set
-        if !state
-            .created_items_roots
-            .contains(&payload.created_items_root)
-        {
-            bail!(
-                "created_items_root {} not in created_items_roots",
-                payload.created_items_root
-            );
-        }
-
-        // Check that output is unique
-        if state.created_items.contains(&Value::from(payload.item)) {
-            bail!("item {} exists in created_items", payload.item);
-        }
-
-        // Check that inputs are unique
-        for nullifier in &payload.nullifiers {
-            if state.nullifiers.contains(nullifier) {
-                bail!("nullifier {} exists in nullifiers", nullifier);
-            }
-        }
+        Ok(Payload::decode_batch(&bytes, &self.common_circuit_data)?)
+    }
 
+    /// Verifies the proof of `payload`, which doesn't depend on the state.
+    fn verify_payload(&self, payload: &Payload) -> Result<(), Rejected> {
         let nullifiers_set = Value::from(
             Set::new(
                 self.params.max_depth_mt_containers,
@@ -426,41 +890,55 @@ impl Node {
             )
             .unwrap(),
         );
-        let st_commit_creation = Statement::Custom(
-            self.pred_commit_creation.clone(),
-            vec![
-                Value::from(payload.item),
-                nullifiers_set,
-                Value::from(payload.created_items_root),
-            ],
-        );
+        let st_commit = match payload.kind {
+            PayloadKind::Creation { item } => Statement::Custom(
+                self.pred_commit_creation.clone(),
+                vec![
+                    Value::from(item),
+                    nullifiers_set,
+                    Value::from(payload.created_items_root),
+                ],
+            ),
+            PayloadKind::Destruction => Statement::Custom(
+                self.pred_commit_destruction.clone(),
+                vec![nullifiers_set, Value::from(payload.created_items_root)],
+            ),
+            PayloadKind::Transfer { item } => Statement::Custom(
+                self.pred_commit_transfer.clone(),
+                vec![
+                    Value::from(item),
+                    nullifiers_set,
+                    Value::from(payload.created_items_root),
+                ],
+            ),
+        };
 
         // Check the proof and ignore invalid ones
-        self.verify_shrunk_main_pod(payload.proof, st_commit_creation)?;
+        self.verify_shrunk_main_pod(payload.proof.clone(), st_commit)
+            .reject(RejectionReason::BadProof)
+    }
 
-        // Register nullifiers
-        for nullifier in &payload.nullifiers {
-            state.nullifiers.insert(*nullifier);
-        }
-        // Register item
-        state
-            .created_items
-            .insert(&Value::from(payload.item))
-            .unwrap();
+    /// Validates the verified `payload` on top of `head` and applies it.
+    fn apply_payload(
+        &self,
+        head: &mut State,
+        payload: Payload,
+        provenance: Provenance,
+    ) -> Result<StateUpdate, Rejected> {
+        head.check_payload(&payload)?;
 
-        state.epoch += 1;
-        let created_items_root = state.created_items.commitment();
-        state
-            .created_items_roots
-            .push(RawValue::from(created_items_root));
-        info!(
-            "state update: ecpoh={}, created_items.len={}, nullifiers.len={}, created_items_root={}, ",
-            state.epoch,
-            state.created_items.set().len(),
-            state.nullifiers.len(),
-            created_items_root,
+        let update = StateUpdate {
+            item: payload.kind.item(),
+            nullifiers: payload.nullifiers,
+            provenance,
+        };
+        head.apply(&update);
+        debug!(
+            "head update: epoch={}, created_items_root={}",
+            head.epoch,
+            head.created_items_roots.last().expect("not empty"),
         );
-        Ok(())
+        Ok(update)
     }
 
     fn verify_shrunk_main_pod(&self, proof: PayloadProof, st: Statement) -> Result<()> {
@@ -468,22 +946,99 @@ impl Node {
         let public_inputs = [sts_hash.0, self.vds_root.0].concat();
         let shrunk_main_pod_proof = match proof {
             PayloadProof::Plonky2(proof) => proof,
-            PayloadProof::Groth16(_) => todo!(),
+            PayloadProof::Groth16(proof) => {
+                // The verifying key is only loaded when running with groth16
+                if self.cfg.proof_type != ProofType::Groth16 {
+                    bail!("groth16 proofs not accepted with PROOF_TYPE=plonky2");
+                }
+                // The Groth16 circuit verifies a plonky2 proof of the MainPod with the same
+                // public inputs as the shrunk one.
+                return common::groth::verify(proof, public_inputs);
+            }
         };
         let proof_with_pis = CompressedProofWithPublicInputs {
             proof: *shrunk_main_pod_proof,
             public_inputs,
         };
-        let proof = proof_with_pis
-            .decompress(
-                &self.verifier_circuit_data.verifier_only.circuit_digest,
-                &self.common_circuit_data,
-            )
-            .unwrap();
+        let proof = proof_with_pis.decompress(
+            &self.verifier_circuit_data.verifier_only.circuit_digest,
+            &self.common_circuit_data,
+        )?;
         self.verifier_circuit_data.verify(proof)
     }
 }
 
+/// Promotes the finalized pending blocks and stores a snapshot every `cfg.snapshot_interval`
+/// finalized slots.
+fn sync_finalized(node: &Node, snapshot_slot: &mut u32) -> Result<()> {
+    if !node.promote_finalized() || node.cfg.snapshot_interval == 0 {
+        return Ok(());
+    }
+    if let Some(slot) = node.finalized_slot()
+        && slot >= *snapshot_slot + node.cfg.snapshot_interval
+    {
+        node.store_snapshot()?;
+        *snapshot_slot = slot;
+    }
+    Ok(())
+}
+
+/// Processes the slots from `node.next_slot()` up to `head_slot` (inclusive).
+async fn catch_up(node: &Node, head_slot: u32, snapshot_slot: &mut u32) -> Result<()> {
+    loop {
+        sync_finalized(node, snapshot_slot)?;
+        let slot = node.next_slot();
+        if slot > head_slot {
+            return Ok(());
+        }
+        debug!("checking slot {}", slot);
+        match node
+            .beacon_cli
+            .get_block_header(BlockId::Slot(slot))
+            .await?
+        {
+            Some(beacon_block_header) => {
+                node.process_beacon_block_header(&beacon_block_header)
+                    .await?;
+            }
+            None => {
+                debug!("slot {} has empty block", slot);
+                node.skip_slot(slot);
+                continue;
+            }
+        };
+
+        if node.cfg.request_rate != 0 {
+            let requests = 5;
+            let delay_ms = 1000 * requests / node.cfg.request_rate;
+            sleep(Duration::from_millis(delay_ms)).await;
+        }
+    }
+}
+
+/// Handles a message from the beacon event stream.
+async fn process_event(
+    node: &Node,
+    event: &str,
+    data: &str,
+    snapshot_slot: &mut u32,
+) -> Result<()> {
+    match event {
+        "head" => {
+            let data: HeadEventData = serde_json::from_str(data)?;
+            debug!("new head {} at slot {}", data.block, data.slot);
+            catch_up(node, data.slot, snapshot_slot).await?;
+        }
+        "finalized_checkpoint" => {
+            let data: FinalizedCheckpointEventData = serde_json::from_str(data)?;
+            node.update_finalized(BlockId::Hash(data.block)).await?;
+            sync_finalized(node, snapshot_slot)?;
+        }
+        event => debug!("ignoring beacon event {}", event),
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     log_init();
@@ -500,12 +1055,6 @@ async fn main() -> Result<()> {
 
     let spec = node.beacon_cli.get_spec().await?;
     info!(?spec, "Beacon spec");
-    let mut head = node
-        .beacon_cli
-        .get_block_header(BlockId::Head)
-        .await?
-        .expect("head is not None");
-    info!(?head, "Beacon head");
 
     {
         let node = node.clone();
@@ -520,56 +1069,57 @@ async fn main() -> Result<()> {
     }
     info!("Started HTTP server");
 
-    let mut slot = node.cfg.do_genesis_slot;
+    let mut snapshot_slot = node.finalized_slot().unwrap_or_default();
     loop {
-        debug!("checking slot {}", slot);
-        let some_beacon_block_header = if slot <= head.slot {
-            node.beacon_cli
-                .get_block_header(BlockId::Slot(slot))
-                .await?
-        } else {
-            // TODO: Be more fancy and replace this with a stream from an event subscription to
-            // Beacon Headers
-            tokio::time::sleep(Duration::from_secs(5)).await;
-            loop {
-                head = node
-                    .beacon_cli
-                    .get_block_header(BlockId::Head)
-                    .await?
-                    .expect("head is not None");
-                if head.slot > slot {
-                    debug!(
-                        "head is {}, slot {} was skipped, retrieving...",
-                        head.slot, slot
-                    );
-                    break node
-                        .beacon_cli
-                        .get_block_header(BlockId::Slot(slot))
-                        .await?;
-                } else if head.slot == slot {
-                    break Some(head.clone());
-                }
-                tokio::time::sleep(Duration::from_secs(1)).await;
-            }
-        };
-        let beacon_block_header = match some_beacon_block_header {
-            Some(block) => block,
-            None => {
-                debug!("slot {} has empty block", slot);
-                slot += 1;
+        // Catch up by polling first, which also covers the slots and checkpoints missed while
+        // the event stream was disconnected.
+        if let Err(e) = node.update_finalized(BlockId::Finalized).await {
+            info!("Failed to get finalized block: {:?}", e);
+        }
+        let head = node
+            .beacon_cli
+            .get_block_header(BlockId::Head)
+            .await?
+            .expect("head is not None");
+        info!(?head, "Beacon head");
+        catch_up(&node, head.slot, &mut snapshot_slot).await?;
+
+        let mut events = match node
+            .beacon_cli
+            .subscribe_to_events(&[Topic::Head, Topic::FinalizedCheckpoint])
+        {
+            Ok(events) => events,
+            Err(e) => {
+                info!("Failed to subscribe to beacon events: {}", e);
+                sleep(EVENTS_RECONNECT_DELAY).await;
                 continue;
             }
         };
-
-        node.process_beacon_block_header(&beacon_block_header)
-            .await?;
-
-        if node.cfg.request_rate != 0 {
-            let requests = 5;
-            let delay_ms = 1000 * requests / node.cfg.request_rate;
-            sleep(Duration::from_millis(delay_ms)).await;
+        loop {
+            let event = match timeout(EVENTS_TIMEOUT, events.next()).await {
+                Ok(Some(event)) => event,
+                Ok(None) => break,
+                Err(_) => {
+                    info!("No beacon events for {:?}, reconnecting", EVENTS_TIMEOUT);
+                    break;
+                }
+            };
+            match event {
+                Ok(Event::Open) => debug!("subscribed to beacon events"),
+                Ok(Event::Message(msg)) => {
+                    if let Err(e) =
+                        process_event(&node, &msg.event, &msg.data, &mut snapshot_slot).await
+                    {
+                        info!("Failed to process {} event: {:?}", msg.event, e);
+                    }
+                }
+                Err(e) => {
+                    info!("Beacon event stream error: {}", e);
+                    break;
+                }
+            }
         }
-
-        slot += 1;
+        events.close();
+        sleep(EVENTS_RECONNECT_DELAY).await;
     }
 }
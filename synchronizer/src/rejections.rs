@@ -0,0 +1,159 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions, create_dir_all},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use alloy::primitives::{Address, B256};
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Why a payload was not applied to the state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionReason {
+    /// The blob doesn't contain a well formed payload
+    DecodeError,
+    /// The payload was proven against a created items root that the synchronizer never published
+    StaleRoot,
+    /// The item already exists
+    DuplicateItem,
+    /// One of the nullifiers has already been published
+    SpentNullifier,
+    /// The payload is a destruction without nullifiers
+    EmptyDestruction,
+    /// The proof doesn't verify
+    BadProof,
+}
+
+/// A payload rejection, carrying its category for the journal
+#[derive(Debug, thiserror::Error)]
+#[error("{reason:?}: {source:#}")]
+pub struct Rejected {
+    pub reason: RejectionReason,
+    pub source: anyhow::Error,
+}
+
+impl Rejected {
+    pub fn new(reason: RejectionReason, source: impl Into<anyhow::Error>) -> Self {
+        Self {
+            reason,
+            source: source.into(),
+        }
+    }
+}
+
+/// Tags the error of a `Result` with a `RejectionReason`.
+pub trait RejectExt<T> {
+    fn reject(self, reason: RejectionReason) -> Result<T, Rejected>;
+}
+
+impl<T, E: Into<anyhow::Error>> RejectExt<T> for Result<T, E> {
+    fn reject(self, reason: RejectionReason) -> Result<T, Rejected> {
+        self.map_err(|e| Rejected::new(reason, e))
+    }
+}
+
+/// Journal entry for a rejected payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rejection {
+    pub slot: u32,
+    pub beacon_root: B256,
+    pub tx_hash: B256,
+    pub sender: Address,
+    pub blob_index: u32,
+    // Index of the payload within the blob.  `None` when the blob couldn't be decoded.
+    pub payload_index: Option<u32>,
+    pub reason: RejectionReason,
+    pub message: String,
+}
+
+impl Rejection {
+    fn key(&self) -> (B256, u32, Option<u32>) {
+        (self.beacon_root, self.blob_index, self.payload_index)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    by_tx: HashMap<B256, Vec<Rejection>>,
+    // Blocks may be processed again after a restart or a reorg, so entries are deduplicated
+    keys: HashSet<(B256, u32, Option<u32>)>,
+}
+
+/// Append-only JSON lines journal of rejected payloads, indexed by transaction hash.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    entries: Mutex<Entries>,
+}
+
+impl Journal {
+    /// Opens the journal at `path`, loading its existing entries.  Unreadable lines are skipped.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut entries = Entries::default();
+        match File::open(path) {
+            Ok(file) => {
+                for (line_number, line) in BufReader::new(file).lines().enumerate() {
+                    match serde_json::from_str::<Rejection>(&line?) {
+                        Ok(rejection) => {
+                            entries.keys.insert(rejection.key());
+                            entries
+                                .by_tx
+                                .entry(rejection.tx_hash)
+                                .or_default()
+                                .push(rejection);
+                        }
+                        Err(e) => warn!("Skipping rejections journal line {}: {}", line_number, e),
+                    }
+                }
+                info!("Loaded {} rejections from {:?}", entries.keys.len(), path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(anyhow!("open {:?}: {}", path, e)),
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Records a rejection, both in memory and on disk.
+    pub fn record(&self, rejection: Rejection) {
+        let mut entries = self.entries.lock().expect("lock");
+        if !entries.keys.insert(rejection.key()) {
+            return;
+        }
+        if let Err(e) = self.append(&rejection) {
+            warn!("Failed to write rejections journal: {:?}", e);
+        }
+        entries
+            .by_tx
+            .entry(rejection.tx_hash)
+            .or_default()
+            .push(rejection);
+    }
+
+    fn append(&self, rejection: &Rejection) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut line = serde_json::to_vec(rejection)?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Returns the rejections of the payloads sent in the transaction `tx_hash`.
+    pub fn by_tx(&self, tx_hash: &B256) -> Vec<Rejection> {
+        let entries = self.entries.lock().expect("lock");
+        entries.by_tx.get(tx_hash).cloned().unwrap_or_default()
+    }
+}
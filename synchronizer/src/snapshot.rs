@@ -0,0 +1,198 @@
+use std::{
+    fs::{File, create_dir_all, read_dir, remove_file, rename},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use alloy::primitives::Address;
+use anyhow::{Result, anyhow, bail};
+use pod2::middleware::RawValue;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::State;
+
+/// Bump this whenever the serialized layout of `State` changes so that old snapshots are ignored
+/// instead of being misinterpreted.
+const SNAPSHOT_VERSION: u32 = 1;
+/// Number of snapshots kept on disk.  Older ones are removed after a new one is stored.
+const SNAPSHOTS_KEEP: usize = 3;
+
+/// Configuration that a snapshot was created with.  A snapshot taken with a different
+/// configuration describes a different history and must not be resumed from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotOrigin {
+    pub do_genesis_slot: u32,
+    pub to_addr: Address,
+}
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    origin: &'a SnapshotOrigin,
+    state: &'a State,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    version: u32,
+    origin: SnapshotOrigin,
+    state: State,
+}
+
+fn snapshot_name(slot: u32) -> String {
+    format!("snapshot-{:010}.cbor", slot)
+}
+
+fn snapshot_slot(file_name: &str) -> Option<u32> {
+    file_name
+        .strip_prefix("snapshot-")?
+        .strip_suffix(".cbor")?
+        .parse()
+        .ok()
+}
+
+/// Returns the snapshots found in `dir` sorted from newest to oldest.
+fn list_snapshots(dir: &Path) -> Result<Vec<(u32, PathBuf)>> {
+    let rd = match read_dir(dir) {
+        Err(e) => {
+            if e.kind() == io::ErrorKind::NotFound {
+                return Ok(Vec::new());
+            } else {
+                return Err(e.into());
+            }
+        }
+        Ok(rd) => rd,
+    };
+    let mut snapshots = Vec::new();
+    for entry in rd {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        if let Some(slot) = snapshot_slot(file_name.to_str().unwrap_or("")) {
+            snapshots.push((slot, entry.path()));
+        }
+    }
+    snapshots.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(snapshots)
+}
+
+/// Stores a snapshot of `state` into `dir`, and prunes old snapshots.  The snapshot is named
+/// after the slot of the last block applied to `state`.
+pub fn store(dir: &Path, origin: &SnapshotOrigin, state: &State) -> Result<()> {
+    let slot = state
+        .block
+        .ok_or_else(|| anyhow!("state has no processed blocks"))?
+        .slot;
+    create_dir_all(dir)?;
+    let snapshot = SnapshotRef {
+        version: SNAPSHOT_VERSION,
+        origin,
+        state,
+    };
+    let snapshot_cbor = minicbor_serde::to_vec(&snapshot)?;
+
+    let name = snapshot_name(slot);
+    let snapshot_path = dir.join(&name);
+    let snapshot_path_tmp = dir.join(format!("{}.tmp", name));
+    let mut file_tmp = File::create(&snapshot_path_tmp)?;
+    file_tmp.write_all(&snapshot_cbor)?;
+    file_tmp.sync_all()?;
+    rename(snapshot_path_tmp, &snapshot_path)?;
+    info!(
+        "stored snapshot at slot {} (epoch {}) to {:?}",
+        slot, state.epoch, snapshot_path
+    );
+
+    for (old_slot, old_path) in list_snapshots(dir)?.into_iter().skip(SNAPSHOTS_KEEP) {
+        debug!("removing old snapshot of slot {}", old_slot);
+        remove_file(old_path)?;
+    }
+    Ok(())
+}
+
+fn load(path: &Path, origin: &SnapshotOrigin) -> Result<State> {
+    let mut file = File::open(path)?;
+    let mut snapshot_cbor = Vec::new();
+    file.read_to_end(&mut snapshot_cbor)?;
+    let snapshot: Snapshot = minicbor_serde::from_slice(&snapshot_cbor)?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        bail!(
+            "snapshot version {} != {}",
+            snapshot.version,
+            SNAPSHOT_VERSION
+        );
+    }
+    if &snapshot.origin != origin {
+        bail!("snapshot origin {:?} != {:?}", snapshot.origin, origin);
+    }
+    validate_state(&snapshot.state)?;
+    Ok(snapshot.state)
+}
+
+/// Checks that the state is internally consistent, which catches snapshots that were truncated
+/// or corrupted on disk.
+fn validate_state(state: &State) -> Result<()> {
+    if state.block.is_none() {
+        bail!("state has no processed blocks");
+    }
+    if state.created_items_roots.len() as u64 != state.epoch + 1 {
+        bail!(
+            "created_items_roots.len={} doesn't match epoch={}",
+            state.created_items_roots.len(),
+            state.epoch
+        );
+    }
+    if state.epochs.len() as u64 != state.epoch {
+        bail!(
+            "epochs.len={} doesn't match epoch={}",
+            state.epochs.len(),
+            state.epoch
+        );
+    }
+    let created_items_root = RawValue::from(state.created_items.commitment());
+    let last_root = state
+        .created_items_roots
+        .last()
+        .ok_or_else(|| anyhow!("empty created_items_roots"))?;
+    if *last_root != created_items_root {
+        bail!(
+            "created_items root {} doesn't match last created_items_root {}",
+            created_items_root,
+            last_root
+        );
+    }
+    if state.nullifiers_roots.len() != state.created_items_roots.len() {
+        bail!(
+            "nullifiers_roots.len={} doesn't match epoch={}",
+            state.nullifiers_roots.len(),
+            state.epoch
+        );
+    }
+    let nullifiers_root = RawValue::from(state.nullifiers.commitment());
+    let last_root = state
+        .nullifiers_roots
+        .last()
+        .ok_or_else(|| anyhow!("empty nullifiers_roots"))?;
+    if *last_root != nullifiers_root {
+        bail!(
+            "nullifiers root {} doesn't match last nullifiers_root {}",
+            nullifiers_root,
+            last_root
+        );
+    }
+    Ok(())
+}
+
+/// Loads the newest valid snapshot from `dir` that was created with the same `origin`.
+/// Snapshots that fail to load are skipped.
+pub fn load_latest(dir: &Path, origin: &SnapshotOrigin) -> Result<Option<State>> {
+    for (slot, path) in list_snapshots(dir)? {
+        match load(&path, origin) {
+            Ok(state) => return Ok(Some(state)),
+            Err(e) => {
+                info!("Invalid snapshot of slot {} at {:?}: {:?}", slot, path, e);
+            }
+        }
+    }
+    Ok(None)
+}